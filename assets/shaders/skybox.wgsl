@@ -1,15 +1,13 @@
 #import bevy_pbr::{mesh_view_bindings::globals, forward_io::VertexOutput}
 
 const DURATION: f32 = 20.0;
+const PI: f32 = 3.14159265;
 
 struct SkyboxCustomMaterial {
     time_t0: f32,
-    #ifdef SIXTEEN_BYTE_ALIGNMENT
-      // WebGL2 structs must be 16 byte aligned.
-      _padding_0: f32,
-      _padding_1: f32,
-      _padding_2: f32,
-    #endif
+    // Sun direction of a procedural sky, w is 1 for it and 0 for a cubemap
+    sky1: vec4<f32>,
+    sky2: vec4<f32>,
 }
 
 @group(2) @binding(0)
@@ -49,12 +47,70 @@ fn fbm(p: vec2f) -> f32 {
     return f / 0.9375;
 }
 
+// Analytic atmosphere: Rayleigh and Mie scattering over the air mass along the view and towards the sun
+
+const BETA_RAYLEIGH: vec3f = vec3f(0.046, 0.108, 0.265);
+const BETA_MIE: vec3f = vec3f(0.02, 0.02, 0.02);
+const MIE_G: f32 = 0.76;
+const SUN_INTENSITY: f32 = 40.0;
+const NIGHT_COLOR: vec3f = vec3f(0.004, 0.008, 0.02);
+
+// Relative amount of air crossed towards a direction, 1 at the zenith
+fn air_mass(y: f32) -> f32 {
+    return 1.0 / (max(y, 0.0) * 0.9 + 0.1);
+}
+
+fn rand32(n: vec3f) -> f32 {
+    return fract(sin(dot(n, vec3f(12.9898, 78.233, 37.719))) * 43758.5453);
+}
+
+fn stars(dir: vec3f) -> f32 {
+    let p = dir * 250.0;
+    let cell = floor(p);
+    let star = smoothstep(0.997, 1.0, rand32(cell));
+    let twinkle = 0.7 + 0.3 * sin(globals.time * 3.0 + rand32(cell + 1.0) * 6.28);
+    return star * twinkle * (1.0 - smoothstep(0.1, 0.45, length(fract(p) - 0.5)));
+}
+
+fn procedural_sky(dir: vec3f, sun: vec3f) -> vec4<f32> {
+    let mu = dot(dir, sun);
+    let rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    let mie_phase = (1.0 - MIE_G * MIE_G) / (4.0 * PI * pow(1.0 + MIE_G * MIE_G - 2.0 * MIE_G * mu, 1.5));
+
+    let extinction = BETA_RAYLEIGH + BETA_MIE;
+    // Sunlight reddened on its way through the air, gone once the sun is down
+    let sunlight = SUN_INTENSITY * exp(-extinction * air_mass(sun.y)) * smoothstep(-0.1, 0.02, sun.y);
+    let scattering = (BETA_RAYLEIGH * rayleigh_phase + BETA_MIE * mie_phase) / extinction
+        * (1.0 - exp(-extinction * air_mass(dir.y)));
+
+    var color = sunlight * scattering;
+    color += sunlight * 0.05 * smoothstep(0.9995, 0.9998, mu) * step(0.0, dir.y);
+
+    let night = 1.0 - smoothstep(-0.2, 0.05, sun.y);
+    color += NIGHT_COLOR + night * stars(dir) * step(0.0, dir.y);
+
+    // Darker ground under the horizon
+    color = mix(color, color * 0.3, smoothstep(0.0, -0.2, dir.y));
+
+    return vec4(color, 1.0);
+}
+
+// Either the cubemap sample or the procedural sky
+fn sky(dir: vec3f, texture: vec4<f32>, params: vec4<f32>) -> vec4<f32> {
+    if params.w > 0.5 {
+        return procedural_sky(dir, params.xyz);
+    }
+    return texture;
+}
+
 @fragment
 fn fragment(
     in: VertexOutput
 ) -> @location(0) vec4<f32> {
-    let texture1: vec4<f32> = textureSample(skybox_texture1, skybox_texture1_sampler, in.world_normal);
-    let texture2: vec4<f32> = textureSample(skybox_texture2, skybox_texture2_sampler, in.world_normal);
+    // Both sampled first, outside of the branches
+    let dir = normalize(in.world_normal);
+    let texture1: vec4<f32> = sky(dir, textureSample(skybox_texture1, skybox_texture1_sampler, in.world_normal), material.sky1);
+    let texture2: vec4<f32> = sky(dir, textureSample(skybox_texture2, skybox_texture2_sampler, in.world_normal), material.sky2);
 
     let progress = min((globals.time - material.time_t0) / DURATION, 1.0);
 
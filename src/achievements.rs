@@ -0,0 +1,318 @@
+use bevy::prelude::*;
+use std::collections::BTreeSet;
+
+use crate::{
+    events::{PlatformLanded, RunStarted, ThemeChanged},
+    game::RunSettings,
+    player::{PlayerInput, TuningPreset},
+    profile::{Profile, Profiles},
+    storage,
+    theme::{ThemeCurrent, THEMES},
+    AppState,
+};
+
+const SAVE_KEY: &str = "achievements";
+const TOAST_DURATION: f32 = 4.0;
+
+pub enum AchievementCondition {
+    /// Reach a score without jumping over a platform
+    PointsWithoutMiss(u32),
+    MovingInARow(u32),
+    AllThemesInRun,
+    PointsWithTuning(TuningPreset, u32),
+    Points(u32),
+}
+
+pub struct Achievement {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub condition: AchievementCondition,
+}
+
+pub const ACHIEVEMENTS: &[Achievement] = &[
+    Achievement {
+        id: "first_steps",
+        name: "FIRST STEPS",
+        description: "Score 10 points",
+        condition: AchievementCondition::Points(10),
+    },
+    Achievement {
+        id: "no_shortcuts",
+        name: "NO SHORTCUTS",
+        description: "Reach 100 points without missing a platform",
+        condition: AchievementCondition::PointsWithoutMiss(100),
+    },
+    Achievement {
+        id: "surfer",
+        name: "SURFER",
+        description: "Land on 10 moving platforms in a row",
+        condition: AchievementCondition::MovingInARow(10),
+    },
+    Achievement {
+        id: "sightseer",
+        name: "SIGHTSEER",
+        description: "Visit every theme in one run",
+        condition: AchievementCondition::AllThemesInRun,
+    },
+    Achievement {
+        id: "snappy",
+        name: "QUICK REFLEXES",
+        description: "Score 50 with the snappy movement",
+        condition: AchievementCondition::PointsWithTuning(TuningPreset::Snappy, 50),
+    },
+    Achievement {
+        id: "marathon",
+        name: "MARATHON",
+        description: "Score 1000 points",
+        condition: AchievementCondition::Points(1000),
+    },
+];
+
+/// Unlocked achievements of the current profile, with the progress of the first player's run.
+/// Practice runs don't count
+#[derive(Resource, Default)]
+pub struct Achievements {
+    profile: Option<u32>,
+    unlocked: BTreeSet<String>,
+    points: u32,
+    missed: bool,
+    moving_in_a_row: u32,
+    themes: BTreeSet<&'static str>,
+}
+
+impl Achievements {
+    pub fn unlocked(&mut self, profile: &Profile) -> &BTreeSet<String> {
+        self.load(profile);
+        &self.unlocked
+    }
+
+    fn load(&mut self, profile: &Profile) {
+        if self.profile != Some(profile.id) {
+            self.profile = Some(profile.id);
+            self.unlocked = storage::load(&profile.save_key(SAVE_KEY)).unwrap_or_default();
+        }
+    }
+
+    fn is_met(&self, condition: &AchievementCondition, run_settings: &RunSettings) -> bool {
+        match *condition {
+            AchievementCondition::PointsWithoutMiss(points) => {
+                !self.missed && self.points >= points
+            }
+            AchievementCondition::MovingInARow(count) => self.moving_in_a_row >= count,
+            AchievementCondition::AllThemesInRun => {
+                THEMES.iter().all(|theme| self.themes.contains(theme.id))
+            }
+            AchievementCondition::PointsWithTuning(tuning, points) => {
+                run_settings.tuning == tuning && self.points >= points
+            }
+            AchievementCondition::Points(points) => self.points >= points,
+        }
+    }
+
+    /// Unlock the achievements whose condition is now met, with a toast for each
+    fn check(&mut self, commands: &mut Commands, profile: &Profile, run_settings: &RunSettings) {
+        if run_settings.practicing() {
+            return;
+        }
+
+        self.load(profile);
+
+        let mut changed = false;
+        for achievement in ACHIEVEMENTS {
+            if !self.unlocked.contains(achievement.id)
+                && self.is_met(&achievement.condition, run_settings)
+            {
+                self.unlocked.insert(achievement.id.to_string());
+                changed = true;
+                spawn_toast(commands, achievement);
+            }
+        }
+
+        if changed {
+            storage::save(&profile.save_key(SAVE_KEY), &self.unlocked);
+        }
+    }
+}
+
+pub fn achievements_run_started(
+    trigger: Trigger<RunStarted>,
+    mut achievements: ResMut<Achievements>,
+    theme_current: Option<Res<ThemeCurrent>>,
+) {
+    if trigger.event().player != 0 {
+        return;
+    }
+
+    achievements.points = 0;
+    achievements.missed = false;
+    achievements.moving_in_a_row = 0;
+    achievements.themes.clear();
+
+    if let Some(theme_current) = theme_current {
+        achievements.themes.insert(theme_current.theme.id);
+    }
+}
+
+pub fn achievements_platform_landed(
+    trigger: Trigger<PlatformLanded>,
+    mut commands: Commands,
+    mut achievements: ResMut<Achievements>,
+    profiles: Res<Profiles>,
+    run_settings: Res<RunSettings>,
+) {
+    let landed = trigger.event();
+    if landed.player != 0 {
+        return;
+    }
+
+    achievements.points = landed.points;
+    achievements.missed |= landed.missed > 0;
+    achievements.moving_in_a_row = if landed.moving {
+        achievements.moving_in_a_row + 1
+    } else {
+        0
+    };
+
+    achievements.check(&mut commands, profiles.current(), &run_settings);
+}
+
+pub fn achievements_theme_changed(
+    trigger: Trigger<ThemeChanged>,
+    mut commands: Commands,
+    mut achievements: ResMut<Achievements>,
+    profiles: Res<Profiles>,
+    run_settings: Res<RunSettings>,
+    state: Res<State<AppState>>,
+) {
+    if *state.get() != AppState::Game {
+        return;
+    }
+
+    achievements.themes.insert(trigger.event().theme);
+    achievements.check(&mut commands, profiles.current(), &run_settings);
+}
+
+/// Popup shown for a few seconds when an achievement unlocks
+#[derive(Component)]
+pub struct Toast(Timer);
+
+fn spawn_toast(commands: &mut Commands, achievement: &Achievement) {
+    commands.spawn((
+        Toast(Timer::from_seconds(TOAST_DURATION, TimerMode::Once)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            padding: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        GlobalZIndex(10),
+        Text::new(format!(
+            "ACHIEVEMENT UNLOCKED\n{}\n{}",
+            achievement.name, achievement.description
+        )),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+    ));
+}
+
+// The newest toast shows on top, the older ones below it
+pub fn update_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Toast, &mut Node)>,
+) {
+    let mut toasts: Vec<_> = query.iter_mut().collect();
+    toasts.sort_by(|(_, a, _), (_, b, _)| a.0.elapsed_secs().total_cmp(&b.0.elapsed_secs()));
+
+    for (i, (entity, toast, node)) in toasts.iter_mut().enumerate() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(*entity).despawn_recursive();
+        }
+
+        let top = Val::Px(10.0 + i as f32 * 100.0);
+        if node.top != top {
+            node.top = top;
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct AchievementsScreen;
+
+pub fn spawn_achievements(
+    mut commands: Commands,
+    mut achievements: ResMut<Achievements>,
+    profiles: Res<Profiles>,
+) {
+    let profile = profiles.current();
+    let unlocked = achievements.unlocked(profile);
+
+    let mut text = format!(
+        "ACHIEVEMENTS: {}\n{}/{} UNLOCKED\n",
+        profile.name,
+        ACHIEVEMENTS
+            .iter()
+            .filter(|achievement| unlocked.contains(achievement.id))
+            .count(),
+        ACHIEVEMENTS.len()
+    );
+
+    for achievement in ACHIEVEMENTS {
+        let mark = if unlocked.contains(achievement.id) {
+            "[X]"
+        } else {
+            "[ ]"
+        };
+
+        text = format!(
+            "{text}\n{mark} {}: {}",
+            achievement.name, achievement.description
+        );
+    }
+
+    text = format!("{text}\n\nJUMP OR ESCAPE: BACK");
+
+    commands
+        .spawn((
+            AchievementsScreen,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        ))
+        .with_child((
+            Text::new(text),
+            TextFont {
+                font_size: 26.0,
+                ..default()
+            },
+        ));
+}
+
+pub fn despawn_achievements(
+    mut commands: Commands,
+    query: Query<Entity, With<AchievementsScreen>>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn leave_achievements(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input: PlayerInput,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) || input.jump_just_pressed(0) {
+        next_state.set(AppState::Menu);
+    }
+}
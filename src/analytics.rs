@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+
+use crate::events::{PlayerDied, RunEnded, RunStarted, ThemeChanged};
+
+// Runs written to the log, in place of an analytics backend
+
+pub fn log_run_started(trigger: Trigger<RunStarted>) {
+    let started = trigger.event();
+    info!(
+        "player {} started a {} run, seed {}",
+        started.player,
+        started.mode.name(),
+        started.seed
+    );
+}
+
+pub fn log_player_died(trigger: Trigger<PlayerDied>) {
+    let died = trigger.event();
+    info!(
+        "player {} died ({:?}) at {:.0} with {} points",
+        died.player, died.cause, died.position, died.points
+    );
+}
+
+pub fn log_run_ended(trigger: Trigger<RunEnded>) {
+    let ended = trigger.event();
+    let time = ended
+        .finish_time
+        .map(|time| format!(" in {time:.2}s"))
+        .unwrap_or_default();
+
+    info!(
+        "player {} run ended ({:?}) with {} points{time}",
+        ended.player, ended.reason, ended.points
+    );
+}
+
+pub fn log_theme_changed(trigger: Trigger<ThemeChanged>) {
+    let changed = trigger.event();
+    info!(
+        "theme changed from {} to {}",
+        changed.previous.unwrap_or("none"),
+        changed.theme
+    );
+}
@@ -0,0 +1,149 @@
+use bevy::{audio::Volume, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{
+        JumpBoosted, Jumped, Landed, LandingQuality, PlatformLanded, PlayerDied, ThemeChanged,
+    },
+    storage,
+};
+
+const SAVE_KEY: &str = "audio";
+const VOLUME_STEPS: f32 = 4.0;
+
+/// Volumes from 0 to 1, shared by every profile
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct AudioSettings {
+    pub music: f32,
+    pub effects: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        storage::load(SAVE_KEY).unwrap_or(Self {
+            music: 0.5,
+            effects: 0.75,
+        })
+    }
+}
+
+impl AudioSettings {
+    /// Next volume of the menu buttons, back to silent after the loudest
+    pub fn next_volume(volume: f32) -> f32 {
+        let step = (volume * VOLUME_STEPS).round() + 1.0;
+        if step > VOLUME_STEPS {
+            0.0
+        } else {
+            step / VOLUME_STEPS
+        }
+    }
+}
+
+pub fn save_audio_settings(audio_settings: Res<AudioSettings>) {
+    if audio_settings.is_changed() && !audio_settings.is_added() {
+        storage::save(SAVE_KEY, &*audio_settings);
+    }
+}
+
+#[derive(Resource)]
+pub struct SoundEffects {
+    jump: Handle<AudioSource>,
+    boost: Handle<AudioSource>,
+    land_soft: Handle<AudioSource>,
+    land: Handle<AudioSource>,
+    land_hard: Handle<AudioSource>,
+    platform: Handle<AudioSource>,
+    theme: Handle<AudioSource>,
+    death: Handle<AudioSource>,
+}
+
+pub fn load_sound_effects(mut commands: Commands, assets_server: Res<AssetServer>) {
+    let load = |name: &str| assets_server.load(format!("sounds/{name}.wav"));
+
+    commands.insert_resource(SoundEffects {
+        jump: load("jump"),
+        boost: load("boost"),
+        land_soft: load("land_soft"),
+        land: load("land"),
+        land_hard: load("land_hard"),
+        platform: load("platform"),
+        theme: load("theme"),
+        death: load("death"),
+    });
+}
+
+fn play_effect(
+    commands: &mut Commands,
+    sound: &Handle<AudioSource>,
+    audio_settings: &Res<AudioSettings>,
+) {
+    if audio_settings.effects > 0.0 {
+        commands.spawn((
+            AudioPlayer(sound.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(audio_settings.effects)),
+        ));
+    }
+}
+
+pub fn jump_sound(
+    _trigger: Trigger<Jumped>,
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    sound_effects: Res<SoundEffects>,
+) {
+    play_effect(&mut commands, &sound_effects.jump, &audio_settings);
+}
+
+pub fn boost_sound(
+    _trigger: Trigger<JumpBoosted>,
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    sound_effects: Res<SoundEffects>,
+) {
+    play_effect(&mut commands, &sound_effects.boost, &audio_settings);
+}
+
+pub fn landing_sound(
+    trigger: Trigger<Landed>,
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    sound_effects: Res<SoundEffects>,
+) {
+    let sound = match trigger.event().quality() {
+        LandingQuality::Soft => &sound_effects.land_soft,
+        LandingQuality::Normal => &sound_effects.land,
+        LandingQuality::Hard => &sound_effects.land_hard,
+    };
+
+    play_effect(&mut commands, sound, &audio_settings);
+}
+
+pub fn platform_sound(
+    _trigger: Trigger<PlatformLanded>,
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    sound_effects: Res<SoundEffects>,
+) {
+    play_effect(&mut commands, &sound_effects.platform, &audio_settings);
+}
+
+pub fn death_sound(
+    _trigger: Trigger<PlayerDied>,
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    sound_effects: Res<SoundEffects>,
+) {
+    play_effect(&mut commands, &sound_effects.death, &audio_settings);
+}
+
+pub fn theme_sound(
+    trigger: Trigger<ThemeChanged>,
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    sound_effects: Res<SoundEffects>,
+) {
+    // Not for the first theme, while loading
+    if trigger.event().previous.is_some() {
+        play_effect(&mut commands, &sound_effects.theme, &audio_settings);
+    }
+}
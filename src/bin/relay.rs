@@ -0,0 +1,196 @@
+// Relay server for online races, forwards racer states between the clients of a room.
+// cargo run --bin relay -- [address], listens on 127.0.0.1:9001 by default
+
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "../protocol.rs"]
+mod protocol;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    server::run();
+}
+
+// The relay doesn't run in a browser, this only keeps wasm builds of the package working
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod server {
+    use super::protocol::{
+        decode, encode, ClientMessage, ServerMessage, DEFAULT_RELAY_PORT, PROTOCOL_VERSION,
+    };
+    use std::{
+        collections::HashMap,
+        io::ErrorKind,
+        net::{TcpListener, TcpStream},
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            mpsc::{self, Sender},
+            Arc, Mutex, MutexGuard, PoisonError,
+        },
+        thread,
+        time::Duration,
+    };
+    use tungstenite::{Error, Message};
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+    const MAX_ROOM_SIZE: usize = 16;
+
+    struct Member {
+        id: u32,
+        name: String,
+        sender: Sender<String>,
+    }
+
+    type Rooms = Arc<Mutex<HashMap<String, Vec<Member>>>>;
+
+    // A panicking client thread leaves at worst a stale member, the other rooms keep going
+    fn lock(rooms: &Rooms) -> MutexGuard<'_, HashMap<String, Vec<Member>>> {
+        rooms.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn run() {
+        let address = std::env::args()
+            .nth(1)
+            .unwrap_or_else(|| format!("127.0.0.1:{DEFAULT_RELAY_PORT}"));
+
+        let listener = match TcpListener::bind(&address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("can't listen on {address}: {e}");
+                return;
+            }
+        };
+
+        println!("relay listening on ws://{address}");
+
+        let rooms = Rooms::default();
+        let next_id = Arc::new(AtomicU32::new(1));
+
+        for stream in listener.incoming().flatten() {
+            let rooms = rooms.clone();
+            let id = next_id.fetch_add(1, Ordering::Relaxed);
+
+            thread::spawn(move || handle_client(stream, &rooms, id));
+        }
+    }
+
+    fn broadcast(members: &[Member], from: u32, message: &ServerMessage) {
+        if let Some(text) = encode(message) {
+            for member in members.iter().filter(|member| member.id != from) {
+                let _ = member.sender.send(text.clone());
+            }
+        }
+    }
+
+    fn handle_client(stream: TcpStream, rooms: &Rooms, id: u32) {
+        // The handshake blocks, the timeout would interrupt it over slower connections
+        let Ok(mut socket) = tungstenite::accept(stream) else {
+            return;
+        };
+
+        if socket
+            .get_ref()
+            .set_read_timeout(Some(POLL_INTERVAL))
+            .is_err()
+        {
+            return;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let mut room: Option<String> = None;
+
+        'connection: loop {
+            match socket.read() {
+                Ok(Message::Text(text)) => match decode::<ClientMessage>(&text) {
+                    Some(ClientMessage::Join {
+                        version,
+                        room: name,
+                        name: racer_name,
+                    }) if room.is_none() => {
+                        let mut rooms = lock(rooms);
+                        let room_size = rooms.get(&name).map_or(0, Vec::len);
+
+                        let reply = if version != PROTOCOL_VERSION {
+                            ServerMessage::Rejected {
+                                reason: "VERSION MISMATCH".to_string(),
+                            }
+                        } else if room_size >= MAX_ROOM_SIZE {
+                            ServerMessage::Rejected {
+                                reason: "ROOM FULL".to_string(),
+                            }
+                        } else {
+                            let members = rooms.entry(name.clone()).or_default();
+
+                            // Introduce the racers to each other
+                            for member in members.iter() {
+                                if let Some(text) = encode(&ServerMessage::RacerJoined {
+                                    id: member.id,
+                                    name: member.name.clone(),
+                                }) {
+                                    let _ = sender.send(text);
+                                }
+                            }
+                            broadcast(
+                                members,
+                                id,
+                                &ServerMessage::RacerJoined {
+                                    id,
+                                    name: racer_name.clone(),
+                                },
+                            );
+
+                            members.push(Member {
+                                id,
+                                name: racer_name,
+                                sender: sender.clone(),
+                            });
+                            room = Some(name);
+
+                            ServerMessage::Joined { id }
+                        };
+
+                        if let Some(text) = encode(&reply) {
+                            let _ = sender.send(text);
+                        }
+                    }
+                    Some(ClientMessage::State(state)) => {
+                        if let Some(room) = &room {
+                            let rooms = lock(rooms);
+
+                            if let Some(members) = rooms.get(room) {
+                                broadcast(members, id, &ServerMessage::RacerState { id, state });
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Message::Close(_)) => break,
+                Ok(_) => {}
+                Err(Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => break,
+            }
+
+            for text in receiver.try_iter() {
+                if socket.send(Message::text(text)).is_err() {
+                    break 'connection;
+                }
+            }
+        }
+
+        // Leave the room
+        if let Some(room) = room {
+            let mut rooms = lock(rooms);
+
+            if let Some(members) = rooms.get_mut(&room) {
+                members.retain(|member| member.id != id);
+                broadcast(members, id, &ServerMessage::RacerLeft { id });
+
+                if members.is_empty() {
+                    rooms.remove(&room);
+                }
+            }
+        }
+    }
+}
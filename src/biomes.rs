@@ -0,0 +1,228 @@
+use rand::prelude::*;
+
+// Platforms over which the generation rules blend from one biome to the next
+const BIOME_BLEND: u32 = 4;
+
+/// Course generation rules, active for a number of platforms
+pub struct Biome {
+    pub id: &'static str,
+    pub length_min: u32,
+    pub length_max: u32,
+    pub params: BiomeParams,
+    /// Chance to go down instead of up, `None` keeps the random direction bias
+    pub vertical_bias: Option<f64>,
+    /// Chance to go left instead of right, `None` keeps the random direction bias
+    pub horizontal_bias: Option<f64>,
+    /// Alternate left and right on every platform
+    pub zigzag: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct BiomeParams {
+    pub spacing_min: f32,
+    pub spacing_max: f32,
+    pub forward_min: f32,
+    pub forward_max: f32,
+    pub vertical_up: f32,
+    pub vertical_down: f32,
+    pub horizontal: f32,
+    pub moving_chance_min: f64,
+    pub moving_chance_max: f64,
+    /// Chance to go down instead of up
+    pub vertical_bias: f64,
+    /// Chance to go left instead of right
+    pub horizontal_bias: f64,
+}
+
+impl BiomeParams {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let lerp64 = |a: f64, b: f64| a + (b - a) * t as f64;
+
+        Self {
+            spacing_min: lerp(self.spacing_min, other.spacing_min),
+            spacing_max: lerp(self.spacing_max, other.spacing_max),
+            forward_min: lerp(self.forward_min, other.forward_min),
+            forward_max: lerp(self.forward_max, other.forward_max),
+            vertical_up: lerp(self.vertical_up, other.vertical_up),
+            vertical_down: lerp(self.vertical_down, other.vertical_down),
+            horizontal: lerp(self.horizontal, other.horizontal),
+            moving_chance_min: lerp64(self.moving_chance_min, other.moving_chance_min),
+            moving_chance_max: lerp64(self.moving_chance_max, other.moving_chance_max),
+            vertical_bias: lerp64(self.vertical_bias, other.vertical_bias),
+            horizontal_bias: lerp64(self.horizontal_bias, other.horizontal_bias),
+        }
+    }
+}
+
+const CLASSIC: BiomeParams = BiomeParams {
+    spacing_min: 5.0,
+    spacing_max: 9.0,
+    forward_min: 4.0,
+    forward_max: 8.0,
+    vertical_up: 3.0,
+    vertical_down: 8.0,
+    horizontal: 8.0,
+    moving_chance_min: 0.1,
+    moving_chance_max: 0.5,
+    vertical_bias: 0.0,
+    horizontal_bias: 0.0,
+};
+
+pub const BIOMES: &[Biome] = &[
+    Biome {
+        id: "classic",
+        length_min: 15,
+        length_max: 30,
+        params: CLASSIC,
+        vertical_bias: None,
+        horizontal_bias: None,
+        zigzag: false,
+    },
+    Biome {
+        id: "staircase",
+        length_min: 8,
+        length_max: 14,
+        params: BiomeParams {
+            spacing_min: 5.5,
+            spacing_max: 8.0,
+            vertical_down: 5.0,
+            horizontal: 2.0,
+            moving_chance_max: 0.2,
+            ..CLASSIC
+        },
+        vertical_bias: Some(1.0),
+        horizontal_bias: None,
+        zigzag: false,
+    },
+    Biome {
+        id: "sprint",
+        length_min: 10,
+        length_max: 20,
+        params: BiomeParams {
+            spacing_min: 4.0,
+            spacing_max: 5.5,
+            forward_min: 7.0,
+            forward_max: 8.0,
+            vertical_up: 0.5,
+            vertical_down: 0.5,
+            horizontal: 2.0,
+            moving_chance_min: 0.0,
+            moving_chance_max: 0.1,
+            ..CLASSIC
+        },
+        vertical_bias: Some(0.5),
+        horizontal_bias: Some(0.5),
+        zigzag: false,
+    },
+    Biome {
+        id: "climb",
+        length_min: 6,
+        length_max: 10,
+        params: BiomeParams {
+            spacing_min: 4.5,
+            spacing_max: 6.0,
+            forward_min: 3.0,
+            forward_max: 5.0,
+            vertical_up: 4.0,
+            horizontal: 4.0,
+            moving_chance_max: 0.3,
+            ..CLASSIC
+        },
+        vertical_bias: Some(0.0),
+        horizontal_bias: None,
+        zigzag: false,
+    },
+    Biome {
+        id: "zigzag",
+        length_min: 8,
+        length_max: 16,
+        params: BiomeParams {
+            forward_min: 2.0,
+            forward_max: 4.0,
+            vertical_up: 1.0,
+            vertical_down: 2.0,
+            horizontal: 12.0,
+            ..CLASSIC
+        },
+        vertical_bias: None,
+        horizontal_bias: None,
+        zigzag: true,
+    },
+];
+
+/// Where the generation is in the biome sequence
+#[derive(Clone)]
+pub struct BiomeProgress {
+    current: usize,
+    previous: usize,
+    platforms: u32,
+    length: u32,
+    zig: bool,
+}
+
+impl Default for BiomeProgress {
+    fn default() -> Self {
+        Self {
+            current: 0,
+            previous: 0,
+            platforms: 0,
+            length: BIOMES[0].length_min,
+            zig: false,
+        }
+    }
+}
+
+impl BiomeProgress {
+    pub fn current(&self) -> &'static str {
+        BIOMES[self.current].id
+    }
+
+    /// Generation rules for the next platform, blended with the previous biome at boundaries.
+    /// Random direction biases are used where a biome doesn't force one
+    pub fn params(&self, random_vertical_bias: f64, random_horizontal_bias: f64) -> BiomeParams {
+        let biome_params = |biome: &Biome, zig: bool| BiomeParams {
+            vertical_bias: biome.vertical_bias.unwrap_or(random_vertical_bias),
+            horizontal_bias: if biome.zigzag {
+                if zig {
+                    1.0
+                } else {
+                    0.0
+                }
+            } else {
+                biome.horizontal_bias.unwrap_or(random_horizontal_bias)
+            },
+            ..biome.params
+        };
+
+        let current = biome_params(&BIOMES[self.current], self.zig);
+
+        if self.platforms < BIOME_BLEND {
+            let t = (self.platforms + 1) as f32 / (BIOME_BLEND + 1) as f32;
+            biome_params(&BIOMES[self.previous], self.zig).lerp(&current, t)
+        } else {
+            current
+        }
+    }
+
+    /// Move to the next platform, switching to another biome at random when the current one is over.
+    /// Only `rng` decides, themes follow the biomes rather than the other way around
+    pub fn advance(&mut self, rng: &mut impl Rng) {
+        self.platforms += 1;
+        self.zig = !self.zig;
+
+        if self.platforms >= self.length {
+            let next = (0..BIOMES.len())
+                .filter(|i| *i != self.current)
+                .collect::<Vec<_>>()
+                .choose(rng)
+                .copied()
+                .unwrap_or(0);
+
+            self.previous = self.current;
+            self.current = next;
+            self.platforms = 0;
+            self.length = rng.gen_range(BIOMES[next].length_min..=BIOMES[next].length_max);
+        }
+    }
+}
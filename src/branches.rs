@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::{collections::VecDeque, f32::consts::PI};
+
+pub const BRANCH_CHANCE: f64 = 0.03;
+
+const BRANCH_LENGTH_MIN: f32 = 35.0;
+const BRANCH_LENGTH_MAX: f32 = 55.0;
+
+const RISKY_BRANCH_SPACING: f32 = 9.5;
+const RISKY_BRANCH_WIDTH: f32 = 3.0;
+const RISKY_BRANCH_POINTS: u32 = 2;
+const RISKY_BRANCH_SIZE: f32 = 0.75;
+
+const SAFE_BRANCH_SPACING: f32 = 5.5;
+const SAFE_BRANCH_WIDTH: f32 = 12.0;
+
+/// One route of a fork, planned in advance
+#[derive(Clone)]
+pub struct Branch {
+    pub positions: VecDeque<Vec3>,
+    /// Predecessors of the next platform of this branch
+    pub last_platforms: Vec<u32>,
+    pub points: u32,
+    pub size: f32,
+}
+
+/// Course split into several branches that merge again
+#[derive(Clone)]
+pub struct Fork {
+    pub branches: Vec<Branch>,
+    pub merge: Vec3,
+}
+
+impl Fork {
+    /// Plan a risky short route next to a safe long route, from the platform at `start` to a merge point ahead
+    pub fn plan(rng: &mut impl Rng, start: Vec3, last_platforms: &[u32]) -> Self {
+        let length = rng.gen_range(BRANCH_LENGTH_MIN..BRANCH_LENGTH_MAX);
+        let direction = Vec3::new(1.0, 0.0, rng.gen_range(-0.3..0.3)).normalize();
+        let merge = start + direction * length + Vec3::Y * rng.gen_range(-3.0..2.0);
+
+        // Which side the risky branch goes
+        let side = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+        let perpendicular = direction.cross(Vec3::Y) * side;
+
+        let branch = |width: f32, spacing: f32, points: u32, size: f32| {
+            let curve = |t: f32| start.lerp(merge, t) + perpendicular * width * (t * PI).sin();
+
+            // Approximate the curve length to get the platform count
+            let curve_length = (1..=16)
+                .map(|i| curve(i as f32 / 16.0).distance(curve((i - 1) as f32 / 16.0)))
+                .sum::<f32>();
+
+            let count = ((curve_length / spacing).round() as usize).max(2);
+
+            Branch {
+                positions: (1..count).map(|i| curve(i as f32 / count as f32)).collect(),
+                last_platforms: last_platforms.to_vec(),
+                points,
+                size,
+            }
+        };
+
+        Self {
+            branches: vec![
+                branch(-SAFE_BRANCH_WIDTH, SAFE_BRANCH_SPACING, 1, 1.0),
+                branch(
+                    RISKY_BRANCH_WIDTH,
+                    RISKY_BRANCH_SPACING,
+                    RISKY_BRANCH_POINTS,
+                    RISKY_BRANCH_SIZE,
+                ),
+            ],
+            merge,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.branches
+            .iter()
+            .all(|branch| branch.positions.is_empty())
+    }
+}
@@ -0,0 +1,378 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use crate::{
+    events::{Jumped, Landed, LandingQuality},
+    player::{CameraPivot, Player, PlayerCamera, PlayerInput},
+    storage,
+};
+
+const SAVE_KEY: &str = "camera";
+
+pub const CAMERA_FOV: f32 = PI / 2.0;
+
+/// Extra field of view at full speed
+const FOV_KICK: f32 = 0.25;
+/// No kick at the starting speed, all of it at twice of it
+const FOV_KICK_MIN_SPEED: f32 = 5.0;
+const FOV_KICK_FULL_SPEED: f32 = 10.0;
+const FOV_KICK_SPEED: f32 = 3.0;
+
+const HARD_LANDING_TRAUMA: f32 = 0.5;
+/// Jumping in the coyote time, right before falling
+const NEAR_MISS_TRAUMA: f32 = 0.3;
+const TRAUMA_DECAY: f32 = 1.5;
+const SHAKE_ANGLE: f32 = 0.05;
+const SHAKE_FREQUENCY: f32 = 22.0;
+
+const DIP_DURATION: f32 = 0.25;
+/// Depth of the dip for a landing at `DIP_FULL_IMPACT` or harder
+const DIP_DEPTH: f32 = 0.25;
+const DIP_FULL_IMPACT: f32 = 12.0;
+
+/// Roll per radian per second of turning
+const TILT: f32 = 0.08;
+const MAX_TILT: f32 = 0.1;
+const TILT_SPEED: f32 = 6.0;
+
+/// Behind and above the pivot, looking down at the player
+const CHASE_DISTANCE: f32 = 5.0;
+const CHASE_PITCH: f32 = 0.3;
+/// Kept between the camera and the platforms in its way
+const CHASE_MARGIN: f32 = 0.3;
+const CHASE_MIN_DISTANCE: f32 = 1.5;
+const CHASE_SPEED: f32 = 4.0;
+
+const FREE_LOOK_MAX_YAW: f32 = PI * 0.75;
+const FREE_LOOK_MAX_PITCH: f32 = 1.2;
+/// Time without input before the view goes back to the auto-aim
+const FREE_LOOK_RETURN_DELAY: f32 = 1.0;
+const FREE_LOOK_RETURN_SPEED: f32 = 3.0;
+
+/// How the camera follows a local player
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    /// Third person, behind the player
+    Chase,
+    /// First person, the mouse or right stick looks around
+    FreeLook,
+}
+
+impl CameraMode {
+    pub const ALL: [Self; 3] = [Self::FirstPerson, Self::Chase, Self::FreeLook];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FirstPerson => "FIRST PERSON",
+            Self::Chase => "CHASE",
+            Self::FreeLook => "FREE LOOK",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CameraEffect {
+    FovKick,
+    Shake,
+    Dip,
+    Tilt,
+}
+
+impl CameraEffect {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FovKick => "SPEED FOV",
+            Self::Shake => "SHAKE",
+            Self::Dip => "LANDING DIP",
+            Self::Tilt => "TURN TILT",
+        }
+    }
+}
+
+/// How much of each camera effect to show, from 0 to 1, shared by every profile
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct CameraSettings {
+    pub fov_kick: f32,
+    pub shake: f32,
+    pub dip: f32,
+    pub tilt: f32,
+    #[serde(default)]
+    pub mode: CameraMode,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        storage::load(SAVE_KEY).unwrap_or(Self {
+            fov_kick: 1.0,
+            shake: 1.0,
+            dip: 1.0,
+            tilt: 1.0,
+            mode: CameraMode::default(),
+        })
+    }
+}
+
+impl CameraSettings {
+    const LEVELS: [f32; 3] = [1.0, 0.5, 0.0];
+
+    pub fn level(&self, effect: CameraEffect) -> f32 {
+        match effect {
+            CameraEffect::FovKick => self.fov_kick,
+            CameraEffect::Shake => self.shake,
+            CameraEffect::Dip => self.dip,
+            CameraEffect::Tilt => self.tilt,
+        }
+    }
+
+    /// Next level of the effect's menu button, from full to off, finer levels from the save go back to full
+    pub fn cycle(&mut self, effect: CameraEffect) {
+        let level = match effect {
+            CameraEffect::FovKick => &mut self.fov_kick,
+            CameraEffect::Shake => &mut self.shake,
+            CameraEffect::Dip => &mut self.dip,
+            CameraEffect::Tilt => &mut self.tilt,
+        };
+
+        let index = Self::LEVELS
+            .iter()
+            .position(|l| (l - *level).abs() < f32::EPSILON)
+            .map_or(0, |index| (index + 1) % Self::LEVELS.len());
+
+        *level = Self::LEVELS[index];
+    }
+}
+
+pub fn save_camera_settings(camera_settings: Res<CameraSettings>) {
+    if camera_settings.is_changed() && !camera_settings.is_added() {
+        storage::save(SAVE_KEY, &*camera_settings);
+    }
+}
+
+/// Motion of the camera around its pivot, reacting to the movement
+#[derive(Component, Default)]
+pub struct CameraEffects {
+    fov_kick: f32,
+    trauma: f32,
+    dip_depth: f32,
+    dip_elapsed: f32,
+    tilt: f32,
+    last_yaw: Option<f32>,
+}
+
+impl CameraEffects {
+    fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+}
+
+/// Placement of the camera around its pivot, for the camera mode
+#[derive(Component, Default)]
+pub struct CameraRig {
+    /// Free-look yaw and pitch over the auto-aim
+    look: Vec2,
+    idle: f32,
+    /// Distance behind the pivot, shortened by the platforms in the way
+    distance: f32,
+}
+
+// The mouse or right stick overrides the auto-aim, which takes over again once they're left alone
+pub fn free_look(
+    time: Res<Time>,
+    input: PlayerInput,
+    camera_settings: Res<CameraSettings>,
+    mut q_cameras: Query<(&PlayerCamera, &mut CameraRig)>,
+) {
+    let dt = time.delta_secs();
+
+    for (camera, mut rig) in &mut q_cameras {
+        let look = if camera_settings.mode == CameraMode::FreeLook {
+            input.look(camera.0, dt)
+        } else {
+            Vec2::ZERO
+        };
+
+        if look == Vec2::ZERO {
+            rig.idle += dt;
+            if rig.idle > FREE_LOOK_RETURN_DELAY {
+                rig.look *= (-FREE_LOOK_RETURN_SPEED * dt).exp();
+            }
+        } else {
+            rig.idle = 0.0;
+            rig.look = (rig.look + look).clamp(
+                Vec2::new(-FREE_LOOK_MAX_YAW, -FREE_LOOK_MAX_PITCH),
+                Vec2::new(FREE_LOOK_MAX_YAW, FREE_LOOK_MAX_PITCH),
+            );
+        }
+    }
+}
+
+pub fn camera_landing(
+    trigger: Trigger<Landed>,
+    q_players: Query<&Player>,
+    mut q_cameras: Query<(&PlayerCamera, &mut CameraEffects)>,
+) {
+    let Ok(player) = q_players.get(trigger.entity()) else {
+        return;
+    };
+
+    let landed = trigger.event();
+
+    for (camera, mut effects) in &mut q_cameras {
+        if camera.0 == player.id {
+            effects.dip_depth = DIP_DEPTH * (landed.impact / DIP_FULL_IMPACT).min(1.0);
+            effects.dip_elapsed = 0.0;
+
+            if landed.quality() == LandingQuality::Hard {
+                effects.add_trauma(HARD_LANDING_TRAUMA);
+            }
+        }
+    }
+}
+
+pub fn camera_near_miss(
+    trigger: Trigger<Jumped>,
+    mut q_cameras: Query<(&PlayerCamera, &mut CameraEffects)>,
+) {
+    let jumped = trigger.event();
+    if !jumped.coyote {
+        return;
+    }
+
+    for (camera, mut effects) in &mut q_cameras {
+        if camera.0 == jumped.player {
+            effects.add_trauma(NEAR_MISS_TRAUMA);
+        }
+    }
+}
+
+// Places the camera of the mode around its pivot, then moves it with the effects
+pub fn update_camera_effects(
+    time: Res<Time>,
+    camera_settings: Res<CameraSettings>,
+    spatial_query: SpatialQuery,
+    q_players: Query<&Player>,
+    q_pivots: Query<&GlobalTransform, With<CameraPivot>>,
+    mut q_cameras: Query<(
+        &PlayerCamera,
+        &Parent,
+        &mut CameraEffects,
+        &mut CameraRig,
+        &mut Transform,
+        &mut Projection,
+    )>,
+) {
+    let dt = time.delta_secs();
+
+    for (camera, parent, mut effects, mut rig, mut transform, mut projection) in &mut q_cameras {
+        let speed = q_players
+            .iter()
+            .find(|player| player.id == camera.0)
+            .map_or(0.0, |player| player.forward_speed);
+
+        // FOV kick with the speed
+        let target = ((speed - FOV_KICK_MIN_SPEED) / (FOV_KICK_FULL_SPEED - FOV_KICK_MIN_SPEED))
+            .clamp(0.0, 1.0);
+        effects.fov_kick += (target - effects.fov_kick) * (1.0 - (-FOV_KICK_SPEED * dt).exp());
+
+        if let Projection::Perspective(perspective) = &mut *projection {
+            let fov = CAMERA_FOV + FOV_KICK * effects.fov_kick * camera_settings.fov_kick;
+            if (perspective.fov - fov).abs() > f32::EPSILON {
+                perspective.fov = fov;
+            }
+        }
+
+        // Shake, stronger with the square of the trauma
+        effects.trauma = (effects.trauma - TRAUMA_DECAY * dt).max(0.0);
+        let shake = effects.trauma * effects.trauma * SHAKE_ANGLE * camera_settings.shake;
+        let t = time.elapsed_secs() * SHAKE_FREQUENCY;
+        let shake_pitch = shake * (t.sin() + (t * 2.3).sin() * 0.5);
+        let shake_yaw = shake * ((t * 1.3).cos() + (t * 3.1).sin() * 0.5);
+        let shake_roll = shake * ((t * 0.7).sin() + (t * 2.9).cos() * 0.5);
+
+        // Landing dip, down and back up
+        effects.dip_elapsed += dt;
+        let dip = if effects.dip_elapsed < DIP_DURATION {
+            effects.dip_depth
+                * (PI * effects.dip_elapsed / DIP_DURATION).sin()
+                * camera_settings.dip
+        } else {
+            0.0
+        };
+
+        // Tilt into the turns of the pivot
+        let pivot = q_pivots.get(parent.get()).ok();
+        let yaw = pivot.map_or(0.0, |pivot| {
+            let forward = pivot.forward();
+            forward.x.atan2(forward.z)
+        });
+        let yaw_rate = effects.last_yaw.map_or(0.0, |last_yaw| {
+            // Shortest way around
+            let delta = (yaw - last_yaw + PI).rem_euclid(2.0 * PI) - PI;
+            if dt > 0.0 {
+                delta / dt
+            } else {
+                0.0
+            }
+        });
+        effects.last_yaw = Some(yaw);
+
+        let target = (yaw_rate * TILT).clamp(-MAX_TILT, MAX_TILT);
+        effects.tilt += (target - effects.tilt) * (1.0 - (-TILT_SPEED * dt).exp());
+        let tilt = effects.tilt * camera_settings.tilt;
+
+        // Orbit around the pivot, in front of the platforms in the way
+        let look = Quat::from_euler(EulerRot::YXZ, rig.look.x, rig.look.y, 0.0);
+        let orbit = if camera_settings.mode == CameraMode::Chase {
+            look * Quat::from_rotation_x(-CHASE_PITCH)
+        } else {
+            look
+        };
+
+        let distance = match pivot {
+            Some(pivot) if camera_settings.mode == CameraMode::Chase => {
+                Dir3::new(pivot.rotation() * orbit * Vec3::Z)
+                    .ok()
+                    .and_then(|direction| {
+                        spatial_query.cast_ray_predicate(
+                            pivot.translation(),
+                            direction,
+                            CHASE_DISTANCE + CHASE_MARGIN,
+                            true,
+                            &SpatialQueryFilter::default(),
+                            &|entity| !q_players.contains(entity),
+                        )
+                    })
+                    .map_or(CHASE_DISTANCE, |hit| {
+                        (hit.time_of_impact - CHASE_MARGIN).max(CHASE_MIN_DISTANCE)
+                    })
+            }
+            _ => 0.0,
+        };
+
+        // Straight in front of a platform, smoothly back out
+        rig.distance = if camera_settings.mode == CameraMode::Chase && distance < rig.distance {
+            distance
+        } else {
+            rig.distance + (distance - rig.distance) * (1.0 - (-CHASE_SPEED * dt).exp())
+        };
+
+        let new_transform =
+            Transform::from_translation(orbit * Vec3::Z * rig.distance + Vec3::NEG_Y * dip)
+                .with_rotation(
+                    orbit
+                        * Quat::from_euler(
+                            EulerRot::YXZ,
+                            shake_yaw,
+                            shake_pitch,
+                            tilt + shake_roll,
+                        ),
+                );
+        if *transform != new_transform {
+            *transform = new_transform;
+        }
+    }
+}
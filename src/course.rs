@@ -0,0 +1,427 @@
+use bevy::{prelude::*, utils::HashMap};
+use std::collections::VecDeque;
+
+#[derive(Clone)]
+pub struct CoursePlatform {
+    pub index: u32,
+    pub entity: Entity,
+    pub position: Vec3,
+    /// Distance along the course from the first platform
+    pub distance: f32,
+    /// Points given when the platform is reached
+    pub points: u32,
+    /// Platforms that come right after this one, more than one when the course forks
+    pub successors: Vec<u32>,
+}
+
+/// Progress of one player along the course
+#[derive(Clone, Default)]
+struct Runner {
+    current: Option<u32>,
+    /// Branch picked by the player when the course forks after the current platform
+    choice: Option<u32>,
+    /// Platforms moved forward since the start
+    steps: u32,
+    /// Most steps reached, kept when rewinding
+    farthest: u32,
+    /// Out of the race, the course isn't kept for this player anymore
+    out: bool,
+}
+
+/// Platforms from the last one reached by a player to the last ones spawned, linked in course order
+#[derive(Resource, Clone)]
+pub struct Course {
+    platforms: HashMap<u32, CoursePlatform>,
+    first: Option<u32>,
+    runners: Vec<Runner>,
+    /// Most steps of the leading player, kept when rewinding since the course was spawned that far
+    farthest_lead: u32,
+}
+
+impl Default for Course {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl Course {
+    pub fn new(players: usize) -> Self {
+        Self {
+            platforms: HashMap::default(),
+            first: None,
+            runners: vec![Runner::default(); players],
+            farthest_lead: 0,
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        index: u32,
+        entity: Entity,
+        position: Vec3,
+        points: u32,
+        predecessors: &[u32],
+    ) {
+        let mut distance = 0.0;
+
+        for predecessor in predecessors {
+            if let Some(predecessor) = self.platforms.get_mut(predecessor) {
+                predecessor.successors.push(index);
+                distance = predecessor.distance + predecessor.position.xz().distance(position.xz());
+            }
+        }
+
+        self.first.get_or_insert(index);
+
+        self.platforms.insert(
+            index,
+            CoursePlatform {
+                index,
+                entity,
+                position,
+                distance,
+                points,
+                successors: Vec::new(),
+            },
+        );
+    }
+
+    pub fn get(&self, index: u32) -> Option<&CoursePlatform> {
+        self.platforms.get(&index)
+    }
+
+    pub fn contains(&self, index: u32) -> bool {
+        self.platforms.contains_key(&index)
+    }
+
+    /// Last platform reached by the player
+    pub fn current(&self, player: usize) -> Option<&CoursePlatform> {
+        self.get(self.runners.get(player)?.current?)
+    }
+
+    /// Platforms the player can go to next, one per branch
+    pub fn branches(&self, player: usize) -> impl Iterator<Item = &CoursePlatform> {
+        let successors = match self.current(player) {
+            Some(current) => current.successors.as_slice(),
+            None => self.first.as_slice(),
+        };
+
+        successors.iter().filter_map(|index| self.get(*index))
+    }
+
+    pub fn next(&self, player: usize) -> Option<&CoursePlatform> {
+        self.runners
+            .get(player)
+            .and_then(|runner| runner.choice)
+            .and_then(|choice| {
+                self.branches(player)
+                    .find(|platform| platform.index == choice)
+            })
+            .or_else(|| self.branches(player).next())
+    }
+
+    pub fn next_after_next(&self, player: usize) -> Option<&CoursePlatform> {
+        self.get(*self.next(player)?.successors.first()?)
+    }
+
+    pub fn choice(&self, player: usize) -> Option<u32> {
+        self.runners.get(player)?.choice
+    }
+
+    pub fn choose(&mut self, player: usize, choice: u32) {
+        if let Some(runner) = self.runners.get_mut(player) {
+            runner.choice = Some(choice);
+        }
+    }
+
+    /// Mark the platform at `index` as reached by the player and forget everything that can't be reached anymore.
+    /// Returns how many platforms the leading player moved forward
+    pub fn reach(&mut self, player: usize, index: u32) -> u32 {
+        let Some(advanced) = self.advance(player, index) else {
+            return 0;
+        };
+
+        let runner = &mut self.runners[player];
+        runner.current = Some(index);
+        runner.choice = None;
+        runner.steps += advanced as u32;
+        runner.farthest = runner.farthest.max(runner.steps);
+
+        self.prune();
+
+        let lead = self.lead();
+        let moved = lead.saturating_sub(self.farthest_lead);
+        self.farthest_lead = self.farthest_lead.max(lead);
+        moved
+    }
+
+    /// Landing on the platform at `index` only goes over steps the player already made before a rewind
+    pub fn is_replay(&self, player: usize, index: u32) -> bool {
+        match (self.runners.get(player), self.advance(player, index)) {
+            (Some(runner), Some(advanced)) => runner.steps + advanced as u32 <= runner.farthest,
+            _ => false,
+        }
+    }
+
+    /// Platforms the player jumps over by landing on the platform at `index`
+    pub fn skipped(&self, player: usize, index: u32) -> u32 {
+        self.advance(player, index)
+            .map_or(0, |advanced| advanced.saturating_sub(1) as u32)
+    }
+
+    // Steps from the player's position to `index`, following the course
+    fn advance(&self, player: usize, index: u32) -> Option<usize> {
+        let runner = self.runners.get(player)?;
+
+        match (runner.current, self.first) {
+            (Some(current), _) if current == index => None,
+            (Some(current), _) => Some(self.depth(current, index).unwrap_or(1).max(1)),
+            (None, Some(first)) => Some(self.depth(first, index).unwrap_or(0) + 1),
+            (None, None) => None,
+        }
+    }
+
+    /// The player is out of the race, stop keeping the platforms behind the others
+    pub fn retire(&mut self, player: usize) {
+        if let Some(runner) = self.runners.get_mut(player) {
+            runner.out = true;
+        }
+
+        self.prune();
+    }
+
+    /// Platforms the player went through along the course, landed on or jumped over
+    pub fn steps(&self, player: usize) -> u32 {
+        self.runners
+            .get(player)
+            .map(|runner| runner.steps)
+            .unwrap_or_default()
+    }
+
+    fn lead(&self) -> u32 {
+        self.runners
+            .iter()
+            .map(|runner| runner.steps)
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn prune(&mut self) {
+        let mut reachable = Vec::new();
+
+        for runner in self.runners.iter().filter(|runner| !runner.out) {
+            match runner.current.or(self.first) {
+                Some(index) => reachable.extend(self.reachable_from(index)),
+                // Nothing reached yet, keep everything
+                None => return,
+            }
+        }
+
+        self.platforms.retain(|index, _| reachable.contains(index));
+    }
+
+    /// Go back to an earlier state of the course, keeping the platforms spawned since.
+    /// Platforms of the snapshot whose entity isn't `alive` anymore stay gone
+    pub fn rewind(&mut self, snapshot: Course, alive: impl Fn(Entity) -> bool) {
+        for (index, platform) in snapshot.platforms {
+            if alive(platform.entity) {
+                self.platforms.entry(index).or_insert(platform);
+            }
+        }
+
+        self.first = snapshot.first;
+
+        for (runner, mut previous) in self.runners.iter_mut().zip(snapshot.runners) {
+            previous.farthest = runner.farthest;
+            *runner = previous;
+        }
+    }
+
+    fn depth(&self, from: u32, to: u32) -> Option<usize> {
+        let mut queue = VecDeque::from([(from, 0)]);
+
+        while let Some((index, depth)) = queue.pop_front() {
+            if index == to {
+                return Some(depth);
+            }
+
+            if let Some(platform) = self.get(index) {
+                queue.extend(platform.successors.iter().map(|s| (*s, depth + 1)));
+            }
+        }
+
+        None
+    }
+
+    fn reachable_from(&self, index: u32) -> Vec<u32> {
+        let mut reachable = vec![index];
+        let mut i = 0;
+
+        while i < reachable.len() {
+            if let Some(platform) = self.get(reachable[i]) {
+                for successor in &platform.successors {
+                    if !reachable.contains(successor) {
+                        reachable.push(*successor);
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        reachable
+    }
+
+    /// How far `position` is between the player's current and next platforms, from 0.0 to 1.0
+    pub fn segment_progress(&self, player: usize, position: Vec3) -> f32 {
+        let (Some(current), Some(next)) = (self.current(player), self.next(player)) else {
+            return 0.0;
+        };
+
+        let segment = next.position.xz() - current.position.xz();
+        let length_squared = segment.length_squared();
+
+        if length_squared <= f32::EPSILON {
+            return 1.0;
+        }
+
+        ((position.xz() - current.position.xz()).dot(segment) / length_squared).clamp(0.0, 1.0)
+    }
+
+    /// Distance travelled along the course by a player at `position`
+    pub fn distance_travelled(&self, player: usize, position: Vec3) -> f32 {
+        match (self.current(player), self.next(player)) {
+            (Some(current), Some(next)) => {
+                current.distance
+                    + (next.distance - current.distance) * self.segment_progress(player, position)
+            }
+            (Some(current), None) => current.distance,
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 - 1 < 2 - 4 > 6 - 7
+    //         3 - 5
+    fn forked_course(players: usize) -> Course {
+        let mut course = Course::new(players);
+        let links: [(u32, &[u32]); 8] = [
+            (0, &[]),
+            (1, &[0]),
+            (2, &[1]),
+            (3, &[1]),
+            (4, &[2]),
+            (5, &[3]),
+            (6, &[4, 5]),
+            (7, &[6]),
+        ];
+
+        for (index, predecessors) in links {
+            course.push(
+                index,
+                Entity::from_raw(index),
+                Vec3::X * index as f32,
+                1,
+                predecessors,
+            );
+        }
+
+        course
+    }
+
+    #[test]
+    fn reach_across_fork() {
+        let mut course = forked_course(1);
+
+        assert_eq!(course.reach(0, 0), 1);
+        assert_eq!(course.reach(0, 1), 1);
+        assert_eq!(course.branches(0).count(), 2);
+
+        // Jumping straight to the second platform of a branch
+        assert_eq!(course.skipped(0, 4), 1);
+        assert_eq!(course.reach(0, 4), 2);
+        assert_eq!(course.current(0).map(|p| p.index), Some(4));
+        assert_eq!(course.steps(0), 4);
+
+        // Both branches merge back, the step count doesn't depend on the branch
+        assert_eq!(course.skipped(0, 6), 0);
+        assert_eq!(course.reach(0, 6), 1);
+        assert_eq!(course.steps(0), 5);
+
+        // Landing again on the current platform isn't progress
+        assert_eq!(course.reach(0, 6), 0);
+    }
+
+    #[test]
+    fn prune_other_branch() {
+        let mut course = forked_course(1);
+
+        // Nothing is pruned before the first landing
+        assert!((0..8).all(|index| course.contains(index)));
+
+        course.reach(0, 1);
+        assert!(!course.contains(0));
+        assert!(course.contains(2) && course.contains(3));
+
+        course.reach(0, 2);
+        assert!(!course.contains(1));
+        assert!(!course.contains(3) && !course.contains(5));
+        assert!(course.contains(4) && course.contains(6));
+    }
+
+    #[test]
+    fn prune_keeps_every_runner_branch() {
+        let mut course = forked_course(2);
+
+        course.reach(0, 4);
+        // The second player hasn't landed yet, everything is kept for them
+        assert!(course.contains(0) && course.contains(5));
+
+        course.reach(1, 3);
+        assert!(!course.contains(1) && !course.contains(2));
+        assert!(course.contains(3) && course.contains(4) && course.contains(5));
+
+        course.retire(1);
+        assert!(!course.contains(3) && !course.contains(5));
+        assert!(course.contains(4));
+    }
+
+    #[test]
+    fn rewind_replays_reached_platforms() {
+        let mut course = forked_course(1);
+
+        course.reach(0, 1);
+        let snapshot = course.clone();
+        course.reach(0, 2);
+        course.reach(0, 4);
+
+        course.rewind(snapshot, |_| true);
+        assert_eq!(course.current(0).map(|p| p.index), Some(1));
+        assert!(course.contains(3));
+
+        // The other branch goes over the same steps, the leading player didn't move forward
+        assert!(course.is_replay(0, 3));
+        assert_eq!(course.reach(0, 3), 0);
+        assert!(course.is_replay(0, 5));
+        assert_eq!(course.reach(0, 5), 0);
+
+        assert!(!course.is_replay(0, 6));
+        assert_eq!(course.reach(0, 6), 1);
+    }
+
+    #[test]
+    fn rewind_drops_despawned_platforms() {
+        let mut course = forked_course(1);
+
+        course.reach(0, 1);
+        let snapshot = course.clone();
+        course.reach(0, 2);
+
+        course.rewind(snapshot, |entity| entity != Entity::from_raw(3));
+        assert!(!course.contains(3));
+        assert!(course.contains(2));
+    }
+}
@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    game::{Game, GameMode, RunSettings},
+    profile::{Profile, Profiles},
+    run_code::RunCode,
+    storage,
+};
+
+const SAVE_KEY: &str = "daily";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UtcDate {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl UtcDate {
+    pub fn today() -> Self {
+        Self::from_days_since_epoch(unix_time_secs().div_euclid(86400))
+    }
+
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    fn from_days_since_epoch(days: i64) -> Self {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self { year, month, day }
+    }
+
+    /// Course seed shared by everyone on that day
+    pub fn seed(&self) -> u64 {
+        self.year as u64 * 10000 + self.month as u64 * 100 + self.day as u64
+    }
+}
+
+impl fmt::Display for UtcDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn unix_time_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| {
+            i64::try_from(duration.as_secs()).unwrap_or_default()
+        })
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn unix_time_secs() -> i64 {
+    (js_sys::Date::now() / 1000.0) as i64
+}
+
+/// Today's challenge with the profile's best scores for it, one per difficulty and movement
+#[derive(Resource)]
+pub struct DailyChallenge {
+    pub date: UtcDate,
+    /// By run code, presets change the course and the movement
+    bests: BTreeMap<String, u32>,
+}
+
+impl DailyChallenge {
+    pub fn new(profile: &Profile) -> Self {
+        Self {
+            date: UtcDate::today(),
+            bests: storage::load(&profile.save_key(SAVE_KEY)).unwrap_or_default(),
+        }
+    }
+
+    fn key(&self, run_settings: &RunSettings) -> String {
+        RunCode {
+            seed: self.date.seed(),
+            mode: GameMode::Daily,
+            curve: run_settings.curve,
+            tuning: run_settings.tuning,
+        }
+        .to_string()
+    }
+
+    pub fn best(&self, run_settings: &RunSettings) -> u32 {
+        self.bests
+            .get(&self.key(run_settings))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+pub fn refresh_daily_challenge(mut commands: Commands, profiles: Res<Profiles>) {
+    commands.insert_resource(DailyChallenge::new(profiles.current()));
+}
+
+pub fn track_daily_best(
+    game: Res<Game>,
+    run_settings: Res<RunSettings>,
+    profiles: Res<Profiles>,
+    mut daily: ResMut<DailyChallenge>,
+) {
+    // A shared code can replay an older day, only today's course counts
+    let is_today = run_settings
+        .seed
+        .is_none_or(|seed| seed == daily.date.seed());
+
+    if run_settings.mode != GameMode::Daily
+        || !is_today
+        || run_settings.practicing()
+        || game.points <= daily.best(&run_settings)
+    {
+        return;
+    }
+
+    let key = daily.key(&run_settings);
+    daily.bests.insert(key, game.points);
+    storage::save(&profiles.current().save_key(SAVE_KEY), &daily.bests);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i64, month: u32, day: u32) -> UtcDate {
+        UtcDate { year, month, day }
+    }
+
+    #[test]
+    fn days_since_epoch() {
+        assert_eq!(UtcDate::from_days_since_epoch(0), date(1970, 1, 1));
+        assert_eq!(UtcDate::from_days_since_epoch(-1), date(1969, 12, 31));
+        assert_eq!(UtcDate::from_days_since_epoch(11017), date(2000, 3, 1));
+        assert_eq!(UtcDate::from_days_since_epoch(19782), date(2024, 2, 29));
+        assert_eq!(UtcDate::from_days_since_epoch(19783), date(2024, 3, 1));
+        assert_eq!(UtcDate::from_days_since_epoch(20745), date(2026, 10, 19));
+    }
+
+    #[test]
+    fn seed() {
+        let leap_day = UtcDate::from_days_since_epoch(19782);
+
+        assert_eq!(leap_day.seed(), 20_240_229);
+        assert_eq!(leap_day.to_string(), "2024-02-29");
+    }
+}
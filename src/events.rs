@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::GameMode;
+
+// Gameplay events, triggered by the gameplay systems. Scores, stats, achievements and effects
+// observe them instead of being called from the movement and course code.
+// Player events target the player entity.
+
+/// A player landed on a platform for the first time
+#[derive(Event)]
+pub struct PlatformLanded {
+    pub player: usize,
+    pub index: u32,
+    /// Score of the player after the landing
+    pub points: u32,
+    pub moving: bool,
+    /// Platforms jumped over to get there
+    pub missed: u32,
+}
+
+/// A player jumped over platforms of the course without landing on them
+#[derive(Event)]
+pub struct PlatformMissed {
+    pub player: usize,
+    pub count: u32,
+}
+
+#[derive(Event)]
+pub struct Jumped {
+    pub player: usize,
+    /// The jump happened in the coyote time, after walking off a platform
+    pub coyote: bool,
+}
+
+/// A player kept the jump pressed long enough for the jump boost
+#[derive(Event)]
+pub struct JumpBoosted;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LandingQuality {
+    Soft,
+    Normal,
+    Hard,
+}
+
+/// A player hit the ground after a jump or a fall, on any platform
+#[derive(Event)]
+pub struct Landed {
+    /// Vertical speed at the impact
+    pub impact: f32,
+}
+
+impl Landed {
+    pub fn quality(&self) -> LandingQuality {
+        match self.impact {
+            impact if impact < 4.0 => LandingQuality::Soft,
+            impact if impact < 9.0 => LandingQuality::Normal,
+            _ => LandingQuality::Hard,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum DeathCause {
+    Fall,
+    Hazard,
+    /// Respawn or practice switch
+    Restart,
+}
+
+/// A player fell for good, after the rewind assist had its chance
+#[derive(Event)]
+pub struct PlayerDied {
+    pub player: usize,
+    pub cause: DeathCause,
+    pub position: Vec3,
+    pub points: u32,
+}
+
+/// A player left the spawn with their first jump
+#[derive(Event)]
+pub struct RunStarted {
+    pub player: usize,
+    pub mode: GameMode,
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RunEndReason {
+    Died,
+    /// Reached the goal of a finite course, or the end of a race
+    Finished,
+    /// Back to the menu
+    Quit,
+}
+
+/// A player's run is over, the player may already be respawned
+#[derive(Event)]
+pub struct RunEnded {
+    pub player: usize,
+    pub reason: RunEndReason,
+    pub points: u32,
+    pub finish_time: Option<f32>,
+}
+
+/// A new theme finished loading and is now shown
+#[derive(Event)]
+pub struct ThemeChanged {
+    pub theme: &'static str,
+    pub previous: Option<&'static str>,
+}
@@ -1,29 +1,183 @@
 use avian3d::prelude::*;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    course::Course,
+    daily::{DailyChallenge, UtcDate},
+    events::{PlayerDied, RunEndReason, RunEnded},
+    leaderboard::RunTracker,
+    online::{OnlineRace, OnlineStatus},
     platforms::Platform,
-    player::{Player, SPAWN_POINT},
+    player::{CameraPivot, Player, TuningPreset, SPAWN_POINT},
+    practice::{restart_from_checkpoint, PracticeCheckpoint},
+    profile::Profiles,
+    rewind::RewindBuffer,
+    run_code::RunCode,
+    segments::Hazard,
+    time_attack::{TimeAttack, TIME_ATTACK_DEFAULT_SEED, TIME_ATTACK_PLATFORMS},
     ChangeThemeRandom, PlatformGeneration, SpawnPlatform,
 };
 
-const NB_PLATFORMS_INIT: u32 = 10;
+pub const NB_PLATFORMS_INIT: u32 = 10;
 
 #[derive(Resource, Default)]
 pub struct Game {
     pub started: bool,
+    /// The end of a finite course was reached
+    pub finished: bool,
     pub points: u32,
+    pub curve: DifficultyCurve,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum GameMode {
+    #[default]
+    Endless,
+    /// Finite course from a seed, ending on a goal platform
+    TimeAttack,
+    /// Same course for everyone on a given UTC day
+    Daily,
+}
+
+impl GameMode {
+    pub const ALL: [Self; 3] = [Self::Endless, Self::TimeAttack, Self::Daily];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Endless => "ENDLESS",
+            Self::TimeAttack => "TIME ATTACK",
+            Self::Daily => "DAILY",
+        }
+    }
+}
+
+/// How fast the course gets harder with the score
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum DifficultyCurve {
+    Gentle,
+    #[default]
+    Normal,
+    Steep,
+}
+
+impl DifficultyCurve {
+    pub const ALL: [Self; 3] = [Self::Gentle, Self::Normal, Self::Steep];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gentle => "GENTLE",
+            Self::Normal => "NORMAL",
+            Self::Steep => "STEEP",
+        }
+    }
+
+    /// Difficulty of the course generation after `steps` platforms, one point each
+    pub fn course_difficulty(self, steps: u32) -> f32 {
+        steps as f32 / self.points_per_difficulty()
+    }
+
+    fn points_per_difficulty(self) -> f32 {
+        match self {
+            Self::Gentle => 2000.0,
+            Self::Normal => 1000.0,
+            Self::Steep => 600.0,
+        }
+    }
+}
+
+/// Settings kept from one run to the next
+#[derive(Resource)]
+pub struct RunSettings {
+    pub mode: GameMode,
+    /// Course seed, random for every run when not set
+    pub seed: Option<u64>,
+    /// Practice runs respawn on checkpoints and don't count for high scores
+    pub practice: bool,
+    /// Assist: falling goes back a few seconds, a limited number of times per run
+    pub rewind: bool,
+    pub curve: DifficultyCurve,
+    pub tuning: TuningPreset,
+    /// Local players racing in split screen
+    pub players: usize,
+    /// Racing an online room, alone on the screen but still without assists
+    pub online: bool,
+}
+
+impl Default for RunSettings {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::default(),
+            seed: None,
+            practice: false,
+            rewind: false,
+            curve: DifficultyCurve::default(),
+            tuning: TuningPreset::default(),
+            players: 1,
+            online: false,
+        }
+    }
+}
+
+/// HUD text of a local player
+#[derive(Component)]
+pub struct Hud(pub usize);
+
 impl Game {
     pub fn difficulty(&self) -> f32 {
-        self.points as f32 / 1000.0
+        self.points as f32 / self.curve.points_per_difficulty()
+    }
+}
+
+impl RunSettings {
+    pub fn seed(&self) -> u64 {
+        match (self.seed, self.mode) {
+            (Some(seed), _) => seed,
+            (None, GameMode::TimeAttack) => TIME_ATTACK_DEFAULT_SEED,
+            (None, GameMode::Daily) => UtcDate::today().seed(),
+            (None, GameMode::Endless) => rand::random(),
+        }
+    }
+
+    /// Practice and rewind are single player assists, racers keep them for their next solo run
+    pub fn practicing(&self) -> bool {
+        self.practice && self.players == 1 && !self.online
+    }
+
+    pub fn rewinding(&self) -> bool {
+        self.rewind && self.players == 1 && !self.online
     }
 }
 
-pub fn init_game(mut commands: Commands) {
-    commands.insert_resource(Game::default());
-    commands.insert_resource(PlatformGeneration::default());
+/// Run condition for the single player features: practice, rewind, respawn
+pub fn is_solo(run_settings: Res<RunSettings>) -> bool {
+    run_settings.players == 1
+}
+
+pub fn init_game(mut commands: Commands, run_settings: Res<RunSettings>, profiles: Res<Profiles>) {
+    let seed = run_settings.seed();
+    let mut platform_gen = PlatformGeneration::from_seed(seed);
+
+    if run_settings.mode == GameMode::TimeAttack {
+        platform_gen.goal = Some(TIME_ATTACK_PLATFORMS);
+        commands.insert_resource(TimeAttack::new(
+            RunCode::new(&run_settings, seed),
+            profiles.current(),
+        ));
+    }
+
+    if run_settings.mode == GameMode::Daily {
+        commands.insert_resource(DailyChallenge::new(profiles.current()));
+    }
+
+    commands.insert_resource(Game {
+        curve: run_settings.curve,
+        ..default()
+    });
+    commands.insert_resource(platform_gen);
+    commands.insert_resource(Course::new(run_settings.players));
+    commands.insert_resource(RewindBuffer::default());
+    commands.insert_resource(RunTracker::default());
 
     for _ in 0..NB_PLATFORMS_INIT {
         commands.trigger(SpawnPlatform);
@@ -33,9 +187,14 @@ pub fn init_game(mut commands: Commands) {
 pub fn init_hud(mut commands: Commands) {
     commands.trigger(ChangeThemeRandom);
 
-    commands.spawn((
+    commands.spawn(hud(0, "Loading..."));
+}
+
+pub fn hud(player: usize, text: &str) -> impl Bundle {
+    (
+        Hud(player),
         Label,
-        Text::new("Loading..."),
+        Text::new(text),
         TextFont {
             font_size: 30.0,
             ..default()
@@ -44,39 +203,212 @@ pub fn init_hud(mut commands: Commands) {
             margin: UiRect::all(Val::Px(5.)),
             ..default()
         },
-    ));
+    )
 }
 
-pub fn update_hud(game: Res<Game>, mut query: Query<&mut Text, With<Label>>) {
-    if game.is_added() || game.is_changed() {
-        let mut text = query.single_mut();
-        if game.started {
-            text.0 = format!("Score: {}", game.points);
+pub fn update_hud(
+    run_settings: Res<RunSettings>,
+    rewind: Res<RewindBuffer>,
+    time_attack: Res<TimeAttack>,
+    daily: Res<DailyChallenge>,
+    course: Res<Course>,
+    online_race: Option<Res<OnlineRace>>,
+    q_players: Query<(&Player, &Transform)>,
+    mut query: Query<(&mut Text, &Hud)>,
+) {
+    for (mut text, hud_player) in &mut query {
+        let Some((player, transform)) = q_players.iter().find(|(p, _)| p.id == hud_player.0) else {
+            continue;
+        };
+
+        let mut hud = if player.started {
+            format!(
+                "Score: {}\n{:.0}m",
+                player.points,
+                course.distance_travelled(player.id, transform.translation)
+            )
         } else {
-            text.0 = "JUMP TO START".to_string();
+            "JUMP TO START".to_string()
+        };
+
+        if run_settings.mode == GameMode::TimeAttack {
+            let time = player
+                .finish_time
+                .unwrap_or_else(|| time_attack.stopwatch.elapsed_secs());
+
+            hud = format!("{hud}\n{time:.2}s");
+
+            if let Some(delta) = time_attack.split_delta() {
+                hud = format!("{hud} ({delta:+.2})");
+            }
+
+            if player.finish_time.is_some() {
+                hud = format!("{hud}\nFINISHED");
+
+                if let Some(best) = &time_attack.best {
+                    hud = format!("{hud}\nBEST: {:.2}s", best.time);
+                }
+            }
+        }
+
+        if run_settings.mode == GameMode::Daily {
+            hud = format!(
+                "{hud}\nDAILY {} BEST: {}",
+                daily.date,
+                daily.best(&run_settings)
+            );
+        }
+
+        if let Some(online_race) = online_race.as_ref().filter(|_| player.id == 0) {
+            let online = match &online_race.status {
+                OnlineStatus::Connecting => "CONNECTING".to_string(),
+                OnlineStatus::Failed(reason) => reason.clone(),
+                OnlineStatus::Joined(_) => {
+                    let distance = course.distance_travelled(player.id, transform.translation);
+                    let rank = format!(
+                        "RANK {}/{}",
+                        online_race.rank(distance),
+                        online_race.racers.len() + 1
+                    );
+
+                    match online_race.ahead(distance) {
+                        Some(racer) => format!("{rank}\nCHASING {}", racer.name),
+                        None => rank,
+                    }
+                }
+            };
+
+            hud = format!("{hud}\nONLINE: {online}");
+        }
+
+        if run_settings.rewinding() {
+            hud = format!("{hud}\nREWINDS: {}", rewind.uses_left);
+        }
+
+        if run_settings.practicing() {
+            hud.insert_str(0, "PRACTICE\n");
+        }
+
+        if run_settings.players > 1 {
+            hud.insert_str(0, &format!("PLAYER {}\n", player.id + 1));
+
+            if player.out {
+                hud = format!("{hud}\nOUT");
+            }
+        }
+
+        if text.0 != hud {
+            text.0 = hud;
         }
     }
 }
 
+/// Alone, dying restarts the run, from the last checkpoint in practice
 pub fn reset(
+    trigger: Trigger<PlayerDied>,
+    mut commands: Commands,
+    game: Res<Game>,
+    run_settings: Res<RunSettings>,
+    profiles: Res<Profiles>,
+    checkpoint: Option<Res<PracticeCheckpoint>>,
+    mut rewind: ResMut<RewindBuffer>,
+    mut q_players: Query<(&mut Transform, &mut LinearVelocity, &mut Player)>,
+    mut q_cameras: Query<(&mut Transform, &CameraPivot), Without<Player>>,
+    platforms: Query<Entity, Or<(With<Platform>, With<Hazard>)>>,
+) {
+    if run_settings.players > 1 {
+        return;
+    }
+
+    let Ok((mut transform, mut velocity, mut player)) = q_players.get_mut(trigger.entity()) else {
+        return;
+    };
+
+    for entity in &platforms {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (mut camera_transform, camera) in &mut q_cameras {
+        if camera.0 == player.id {
+            camera_transform.look_at(Vec3::X, Vec3::Y);
+        }
+    }
+
+    *velocity = LinearVelocity::ZERO;
+
+    match checkpoint {
+        Some(checkpoint) if run_settings.practicing() => {
+            rewind.forget();
+            transform.translation = checkpoint.position + Vec3::Y * 3.0;
+            player.restart(checkpoint.points);
+            restart_from_checkpoint(commands, &checkpoint, run_settings.curve);
+        }
+        _ => {
+            // A finished run already ended at the goal
+            if !game.finished {
+                commands.trigger_targets(
+                    RunEnded {
+                        player: player.id,
+                        reason: RunEndReason::Died,
+                        points: player.points,
+                        finish_time: player.finish_time,
+                    },
+                    trigger.entity(),
+                );
+            }
+
+            transform.translation = SPAWN_POINT;
+            player.restart(0);
+            init_game(commands, run_settings, profiles);
+        }
+    }
+}
+
+/// Clear the course when going back to the menu, only the first player stays
+pub fn exit_game(
     mut commands: Commands,
-    q_player: Single<(&mut Transform, &mut LinearVelocity), With<Player>>,
-    q_camera: Single<&mut Transform, (With<Camera3d>, Without<Player>)>,
-    platforms: Query<Entity, With<Platform>>,
+    game: Res<Game>,
+    mut q_players: Query<(Entity, &mut Transform, &mut LinearVelocity, &mut Player)>,
+    mut q_cameras: Query<&mut Transform, (With<CameraPivot>, Without<Player>)>,
+    platforms: Query<Entity, Or<(With<Platform>, With<Hazard>)>>,
+    mut q_huds: Query<(Entity, &mut Text, &Hud)>,
 ) {
-    let (mut transform, mut velocity) = q_player.into_inner();
+    for entity in &platforms {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<PracticeCheckpoint>();
+
+    for (entity, mut transform, mut velocity, mut player) in &mut q_players {
+        if player.started && !game.finished {
+            commands.trigger_targets(
+                RunEnded {
+                    player: player.id,
+                    reason: RunEndReason::Quit,
+                    points: player.points,
+                    finish_time: player.finish_time,
+                },
+                entity,
+            );
+        }
 
-    if velocity.y < -20.0 {
-        for entity in &platforms {
+        if player.id == 0 {
+            transform.translation = SPAWN_POINT;
+            *velocity = LinearVelocity::ZERO;
+            player.restart(0);
+        } else {
             commands.entity(entity).despawn_recursive();
         }
+    }
 
-        let mut camera_transform = q_camera.into_inner();
+    for mut camera_transform in &mut q_cameras {
         camera_transform.look_at(Vec3::X, Vec3::Y);
+    }
 
-        transform.translation = SPAWN_POINT;
-        *velocity = LinearVelocity::ZERO;
-
-        init_game(commands);
+    for (entity, mut text, hud) in &mut q_huds {
+        if hud.0 == 0 {
+            text.0.clear();
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
     }
 }
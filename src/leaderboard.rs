@@ -0,0 +1,369 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+use crate::{
+    course::Course,
+    daily::UtcDate,
+    events::RunEnded,
+    game::{Game, GameMode, RunSettings},
+    platforms::PlatformGeneration,
+    player::{Player, PlayerInput, TuningPreset},
+    profile::Profiles,
+    storage, AppState,
+};
+
+const SAVE_KEY: &str = "leaderboard";
+const RUNS_PER_BOARD: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LeaderboardEntry {
+    pub profile: String,
+    pub mode: GameMode,
+    pub seed: u64,
+    pub date: String,
+    pub score: u32,
+    pub distance: f32,
+    pub time: f32,
+    /// Reached the goal of a time attack course
+    pub finished: bool,
+    pub tuning: TuningPreset,
+}
+
+/// Runs compete on the same board: endless courses are random so they all go together,
+/// the other modes are ranked per course seed
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Board {
+    pub mode: GameMode,
+    pub seed: Option<u64>,
+}
+
+impl Board {
+    pub fn name(self) -> String {
+        match (self.mode, self.seed) {
+            (GameMode::Daily, Some(seed)) => format!(
+                "{} {:04}-{:02}-{:02}",
+                self.mode.name(),
+                seed / 10000,
+                seed / 100 % 100,
+                seed % 100
+            ),
+            (mode, Some(seed)) => format!("{} SEED {seed}", mode.name()),
+            (mode, None) => mode.name().to_string(),
+        }
+    }
+}
+
+impl LeaderboardEntry {
+    pub fn board(&self) -> Board {
+        Board {
+            mode: self.mode,
+            seed: (self.mode != GameMode::Endless).then_some(self.seed),
+        }
+    }
+
+    /// Finishers first by time, then by score
+    fn rank(&self, other: &Self) -> Ordering {
+        match (self.finished, other.finished) {
+            (true, true) => self.time.total_cmp(&other.time),
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => other.score.cmp(&self.score),
+        }
+    }
+}
+
+/// Best local runs of every profile
+#[derive(Resource, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Default for Leaderboard {
+    fn default() -> Self {
+        storage::load(SAVE_KEY).unwrap_or_else(|| Self {
+            entries: Vec::new(),
+        })
+    }
+}
+
+impl Leaderboard {
+    pub fn submit(&mut self, entry: LeaderboardEntry) {
+        self.insert(entry);
+        storage::save(SAVE_KEY, self);
+    }
+
+    fn insert(&mut self, entry: LeaderboardEntry) {
+        self.entries.push(entry);
+        self.entries
+            .sort_by(|a, b| a.board().cmp(&b.board()).then_with(|| a.rank(b)));
+
+        // Only keep the top of each board
+        let mut kept: Vec<LeaderboardEntry> = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.drain(..) {
+            let board = entry.board();
+            let rank = kept.iter().rev().take_while(|e| e.board() == board).count();
+
+            if rank < RUNS_PER_BOARD {
+                kept.push(entry);
+            }
+        }
+        self.entries = kept;
+    }
+
+    pub fn boards(&self) -> Vec<Board> {
+        let mut boards: Vec<Board> = self.entries.iter().map(LeaderboardEntry::board).collect();
+        boards.dedup();
+        boards
+    }
+}
+
+/// Progress of the current solo run, submitted to the leaderboard when it ends
+#[derive(Resource, Default)]
+pub struct RunTracker {
+    distance: f32,
+    time: f32,
+    /// Practice was on at some point, the run doesn't count
+    practice: bool,
+}
+
+pub fn track_run(
+    time: Res<Time>,
+    game: Res<Game>,
+    run_settings: Res<RunSettings>,
+    course: Res<Course>,
+    mut tracker: ResMut<RunTracker>,
+    q_players: Query<(&Player, &Transform)>,
+) {
+    let Some((player, transform)) = q_players.iter().find(|(player, _)| player.id == 0) else {
+        return;
+    };
+
+    if !player.started || game.finished {
+        return;
+    }
+
+    tracker.time += time.delta_secs();
+    tracker.distance = tracker
+        .distance
+        .max(course.distance_travelled(player.id, transform.translation));
+    tracker.practice |= run_settings.practicing();
+}
+
+/// A solo run ends at the goal, on a fall that restarts it or back to the menu
+pub fn submit_run(
+    trigger: Trigger<RunEnded>,
+    profiles: Res<Profiles>,
+    run_settings: Res<RunSettings>,
+    platform_gen: Res<PlatformGeneration>,
+    tracker: Res<RunTracker>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    let ended = trigger.event();
+
+    if ended.player != 0 || tracker.practice || run_settings.players > 1 {
+        return;
+    }
+
+    if ended.points == 0 && ended.finish_time.is_none() {
+        return;
+    }
+
+    leaderboard.submit(LeaderboardEntry {
+        profile: profiles.current().name.clone(),
+        mode: run_settings.mode,
+        seed: platform_gen.seed,
+        date: UtcDate::today().to_string(),
+        score: ended.points,
+        distance: tracker.distance,
+        time: ended.finish_time.unwrap_or(tracker.time),
+        finished: ended.finish_time.is_some(),
+        tuning: run_settings.tuning,
+    });
+}
+
+#[derive(Component)]
+pub struct LeaderboardScreen;
+
+#[derive(Component)]
+pub struct LeaderboardText;
+
+/// Board shown on the leaderboard screen
+#[derive(Resource, Default)]
+pub struct LeaderboardView {
+    pub board: usize,
+}
+
+pub fn spawn_leaderboard(mut commands: Commands) {
+    commands
+        .spawn((
+            LeaderboardScreen,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        ))
+        .with_child((
+            LeaderboardText,
+            Text::default(),
+            TextFont {
+                font_size: 28.0,
+                ..default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+        ));
+}
+
+pub fn despawn_leaderboard(mut commands: Commands, query: Query<Entity, With<LeaderboardScreen>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Left/Right to switch boards, jump or Escape to go back
+pub fn update_leaderboard(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input: PlayerInput,
+    leaderboard: Res<Leaderboard>,
+    mut view: ResMut<LeaderboardView>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut text: Single<&mut Text, With<LeaderboardText>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) || input.jump_just_pressed(0) {
+        next_state.set(AppState::Menu);
+    }
+
+    let boards = leaderboard.boards();
+    let count = boards.len().max(1);
+
+    if keyboard_input.any_just_pressed([KeyCode::ArrowRight, KeyCode::KeyD]) {
+        view.board = (view.board + 1) % count;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::ArrowLeft, KeyCode::KeyA]) {
+        view.board = (view.board + count - 1) % count;
+    }
+    view.board = view.board.min(count - 1);
+
+    let mut value = "LEADERBOARD\n".to_string();
+
+    match boards.get(view.board) {
+        Some(&board) => {
+            value = format!("{value}< {} >\n", board.name());
+
+            let entries = leaderboard.entries.iter().filter(|e| e.board() == board);
+            for (rank, entry) in entries.enumerate() {
+                let time = if entry.mode == GameMode::TimeAttack && !entry.finished {
+                    "DNF".to_string()
+                } else {
+                    format!("{:.2}s", entry.time)
+                };
+
+                value = format!(
+                    "{value}\n{}. {}  {} PTS  {:.0}m  {time}  {}  {}",
+                    rank + 1,
+                    entry.profile,
+                    entry.score,
+                    entry.distance,
+                    entry.tuning.name(),
+                    entry.date
+                );
+            }
+        }
+        None => value = format!("{value}\nNO RUNS YET"),
+    }
+
+    value = format!("{value}\n\nLEFT/RIGHT: BOARD  JUMP OR ESCAPE: BACK");
+
+    if text.0 != value {
+        text.0 = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(mode: GameMode, seed: u64, score: u32, time: f32, finished: bool) -> LeaderboardEntry {
+        LeaderboardEntry {
+            profile: "PLAYER".to_string(),
+            mode,
+            seed,
+            date: "2024-02-29".to_string(),
+            score,
+            distance: 0.0,
+            time,
+            finished,
+            tuning: TuningPreset::default(),
+        }
+    }
+
+    fn scores(leaderboard: &Leaderboard, board: Board) -> Vec<u32> {
+        leaderboard
+            .entries
+            .iter()
+            .filter(|e| e.board() == board)
+            .map(|e| e.score)
+            .collect()
+    }
+
+    #[test]
+    fn keeps_top_of_each_board() {
+        let mut leaderboard = Leaderboard {
+            entries: Vec::new(),
+        };
+
+        for score in 1..=12 {
+            leaderboard.insert(entry(
+                GameMode::Endless,
+                u64::from(score),
+                score,
+                0.0,
+                false,
+            ));
+        }
+        leaderboard.insert(entry(GameMode::Daily, 20_240_229, 3, 0.0, false));
+
+        // Endless runs all share a board whatever the seed
+        let endless = Board {
+            mode: GameMode::Endless,
+            seed: None,
+        };
+        assert_eq!(
+            scores(&leaderboard, endless),
+            [12, 11, 10, 9, 8, 7, 6, 5, 4, 3]
+        );
+
+        let daily = Board {
+            mode: GameMode::Daily,
+            seed: Some(20_240_229),
+        };
+        assert_eq!(scores(&leaderboard, daily), [3]);
+        assert_eq!(leaderboard.boards(), [endless, daily]);
+
+        // A run below the top doesn't make it
+        leaderboard.insert(entry(GameMode::Endless, 0, 1, 0.0, false));
+        assert_eq!(scores(&leaderboard, endless).last(), Some(&3));
+    }
+
+    #[test]
+    fn finishers_rank_first() {
+        let mut leaderboard = Leaderboard {
+            entries: Vec::new(),
+        };
+
+        leaderboard.insert(entry(GameMode::TimeAttack, 7, 50, 10.0, false));
+        leaderboard.insert(entry(GameMode::TimeAttack, 7, 20, 40.0, true));
+        leaderboard.insert(entry(GameMode::TimeAttack, 7, 30, 30.0, true));
+        leaderboard.insert(entry(GameMode::TimeAttack, 8, 10, 5.0, true));
+
+        let board = Board {
+            mode: GameMode::TimeAttack,
+            seed: Some(7),
+        };
+        assert_eq!(scores(&leaderboard, board), [30, 20, 50]);
+    }
+}
@@ -1,23 +1,76 @@
 use avian3d::prelude::*;
-use bevy::prelude::*;
+use bevy::{audio::AddAudioSource, prelude::*};
 
+mod achievements;
+mod analytics;
+mod audio;
+mod biomes;
+mod branches;
+mod camera;
+mod course;
+mod daily;
+mod events;
 mod game;
+mod leaderboard;
+mod menu;
+mod music;
+mod online;
+mod pause;
+mod photo;
 mod platforms;
 mod player;
+mod practice;
+mod profile;
+mod protocol;
+mod race;
+mod rewind;
+mod run_code;
+mod segments;
 mod skybox;
+mod stats;
+mod storage;
 mod theme;
+mod time_attack;
+mod vfx;
 
+use achievements::*;
+use analytics::*;
+use audio::*;
+use camera::*;
+use course::*;
+use daily::*;
 use game::*;
+use leaderboard::*;
+use menu::*;
+use music::*;
+use online::*;
+use pause::*;
+use photo::*;
 use platforms::*;
 use player::*;
+use practice::*;
+use profile::*;
+use race::*;
+use rewind::*;
+use run_code::*;
+use segments::*;
 use skybox::*;
+use stats::*;
 use theme::*;
+use time_attack::*;
+use vfx::*;
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
-enum AppState {
+pub enum AppState {
     #[default]
     Loading,
+    Menu,
     Game,
+    /// Standings at the end of a local race
+    Results,
+    Leaderboard,
+    Stats,
+    Achievements,
 }
 
 fn main() {
@@ -38,33 +91,222 @@ fn main() {
             PhysicsPlugins::default(),
             MaterialPlugin::<SkyboxCustomMaterial>::default(),
         ))
+        .init_asset::<SegmentPrefab>()
+        .init_asset_loader::<SegmentPrefabLoader>()
+        .add_audio_source::<FilteredAudio>()
         .insert_resource(AmbientLight {
             color: Color::WHITE,
             brightness: 1000.0,
         })
         .init_state::<AppState>()
+        .add_sub_state::<PauseState>()
         .init_resource::<Game>()
+        .init_resource::<RunSettings>()
         .init_resource::<PlatformGeneration>()
-        .add_systems(Startup, (spawn_player, init_hud))
-        .add_systems(Update, (apply_loaded_theme,))
-        .add_systems(OnEnter(AppState::Game), (init_game,))
+        .init_resource::<Course>()
+        .init_resource::<RewindBuffer>()
+        .init_resource::<TimeAttack>()
+        .init_resource::<Profiles>()
+        .init_resource::<Leaderboard>()
+        .init_resource::<LeaderboardView>()
+        .init_resource::<RunTracker>()
+        .init_resource::<RunStats>()
+        .init_resource::<Achievements>()
+        .init_resource::<RunCodeInput>()
+        .init_resource::<PlatformColliders>()
+        .init_resource::<OnlineSettings>()
+        .init_resource::<AudioSettings>()
+        .init_resource::<AdaptiveMusic>()
+        .init_resource::<CameraSettings>()
+        .init_resource::<PhotoSettings>()
+        .init_resource::<SkyClock>()
+        .add_systems(
+            Startup,
+            (
+                spawn_player,
+                init_hud,
+                load_segments,
+                load_checkpoint_assets,
+                load_goal_assets,
+                read_startup_run_code,
+                apply_profile_settings,
+                load_ghost_assets,
+                load_sound_effects,
+                load_vfx_assets,
+                load_skybox_assets,
+                spawn_sun,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                finish_platform_colliders,
+                apply_loaded_theme.after(finish_platform_colliders),
+                finish_loading
+                    .after(apply_loaded_theme)
+                    .run_if(in_state(AppState::Loading)),
+                update_sky.after(apply_loaded_theme),
+                save_profile_settings,
+                save_audio_settings,
+                save_camera_settings,
+                update_toasts,
+                start_music,
+                update_music.after(start_music),
+                update_music_filter,
+                update_particles,
+                spawn_boost_glows,
+                update_boost_glows,
+                animate_spawn_in,
+                crumble_platforms,
+            ),
+        )
+        .add_systems(
+            OnEnter(AppState::Menu),
+            (
+                refresh_daily_challenge,
+                spawn_menu.after(refresh_daily_challenge),
+            ),
+        )
+        .add_systems(OnExit(AppState::Menu), (despawn_menu,))
+        .add_systems(
+            Update,
+            (menu_buttons, menu_run_code_input, update_menu).run_if(in_state(AppState::Menu)),
+        )
+        .add_systems(
+            OnEnter(AppState::Game),
+            (init_game, spawn_racers, join_online_race),
+        )
+        .add_systems(
+            OnExit(AppState::Game),
+            (exit_game, remember_run_code, leave_online_race, resume_time),
+        )
+        .add_systems(OnExit(PauseState::Running), (pause_time,))
+        .add_systems(OnEnter(PauseState::Running), (resume_time,))
+        .add_systems(OnEnter(PauseState::Paused), (spawn_pause_screen,))
+        .add_systems(OnExit(PauseState::Paused), (despawn_pause_screen,))
+        .add_systems(Update, (pause_menu,).run_if(in_state(PauseState::Paused)))
+        .add_systems(OnEnter(PauseState::Photo), (enter_photo_mode,))
+        .add_systems(OnExit(PauseState::Photo), (exit_photo_mode,))
+        .add_systems(
+            Update,
+            (
+                fly_photo_camera,
+                adjust_photo_settings,
+                apply_photo_settings.after(adjust_photo_settings),
+                photo_mode_input,
+            )
+                .run_if(in_state(PauseState::Photo)),
+        )
+        .add_systems(OnEnter(AppState::Results), (spawn_results,))
+        .add_systems(OnExit(AppState::Results), (despawn_results,))
+        .add_systems(Update, (leave_results,).run_if(in_state(AppState::Results)))
+        .add_systems(OnEnter(AppState::Leaderboard), (spawn_leaderboard,))
+        .add_systems(OnExit(AppState::Leaderboard), (despawn_leaderboard,))
+        .add_systems(
+            Update,
+            (update_leaderboard,).run_if(in_state(AppState::Leaderboard)),
+        )
+        .add_systems(OnEnter(AppState::Stats), (spawn_stats,))
+        .add_systems(OnExit(AppState::Stats), (despawn_stats,))
+        .add_systems(Update, (leave_stats,).run_if(in_state(AppState::Stats)))
+        .add_systems(OnEnter(AppState::Achievements), (spawn_achievements,))
+        .add_systems(OnExit(AppState::Achievements), (despawn_achievements,))
+        .add_systems(
+            Update,
+            (leave_achievements,).run_if(in_state(AppState::Achievements)),
+        )
+        .add_systems(Update, (update_viewports,))
         .add_systems(
             Update,
             (
                 player_touch_platform,
-                player_hover_platform,
+                player_choose_branch.before(player_movement),
                 player_movement,
                 camera_rotation,
+                free_look,
+                update_camera_effects
+                    .after(camera_rotation)
+                    .after(free_look),
+                update_player_models.after(player_movement),
                 update_moving_platforms,
+                retire_skipped_platforms.after(player_touch_platform),
                 delete_touched_platforms,
-                reset.after(player_movement),
-                force_respawn,
-                force_theme_change,
+                delete_passed_hazards,
+                player_touch_hazard,
+                update_time_attack.after(player_touch_platform),
+                track_daily_best.after(player_touch_platform),
+                track_run.after(player_touch_platform),
+                track_movement_stats.after(player_movement),
+                player_fall
+                    .after(player_movement)
+                    .after(player_touch_hazard)
+                    .after(rewind_on_fall)
+                    .after(force_respawn)
+                    .after(toggle_practice)
+                    .after(track_run),
+                speed_trails.after(player_movement),
                 update_hud,
             )
-                .run_if(in_state(AppState::Game)),
+                .run_if(in_state(PauseState::Running)),
+        )
+        .add_systems(
+            Update,
+            (
+                record_rewind.after(player_movement),
+                rewind_on_fall.after(player_movement),
+                save_checkpoint.after(player_touch_platform),
+                toggle_practice,
+                toggle_rewind,
+                force_respawn,
+            )
+                .run_if(in_state(PauseState::Running).and(is_solo)),
+        )
+        .add_systems(
+            Update,
+            (end_race.after(player_fall).after(update_time_attack),)
+                .run_if(in_state(PauseState::Running).and(not(is_solo))),
+        )
+        .add_systems(
+            Update,
+            (update_online_race, update_ghosts)
+                .run_if(in_state(AppState::Game).and(resource_exists::<OnlineRace>)),
+        )
+        .add_systems(
+            Update,
+            (pause_game, force_theme_change, cycle_platform_collider_mode)
+                .run_if(in_state(PauseState::Running)),
         )
         .add_observer(change_theme)
         .add_observer(spawn_platform)
+        .add_observer(advance_course)
+        .add_observer(reset)
+        .add_observer(race_fall)
+        .add_observer(submit_run)
+        .add_observer(stats_run_started)
+        .add_observer(stats_theme_changed)
+        .add_observer(stats_jumped)
+        .add_observer(stats_platform_landed)
+        .add_observer(stats_platform_missed)
+        .add_observer(stats_player_died)
+        .add_observer(stats_run_ended)
+        .add_observer(achievements_run_started)
+        .add_observer(achievements_platform_landed)
+        .add_observer(achievements_theme_changed)
+        .add_observer(jump_sound)
+        .add_observer(boost_sound)
+        .add_observer(landing_sound)
+        .add_observer(platform_sound)
+        .add_observer(death_sound)
+        .add_observer(theme_sound)
+        .add_observer(crossfade_music)
+        .add_observer(music_run_started)
+        .add_observer(music_milestone)
+        .add_observer(landing_dust)
+        .add_observer(camera_landing)
+        .add_observer(camera_near_miss)
+        .add_observer(log_run_started)
+        .add_observer(log_player_died)
+        .add_observer(log_run_ended)
+        .add_observer(log_theme_changed)
         .run();
 }
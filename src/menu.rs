@@ -0,0 +1,426 @@
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+};
+
+use crate::{
+    audio::AudioSettings,
+    camera::{CameraEffect, CameraMode, CameraSettings},
+    daily::{DailyChallenge, UtcDate},
+    game::{DifficultyCurve, GameMode, RunSettings},
+    online::OnlineRace,
+    player::{TuningPreset, MAX_PLAYERS},
+    profile::{Profiles, PROFILE_NAME_MAX_LEN},
+    run_code::{copy_to_clipboard, paste_from_clipboard, RunCode, RunCodeInput},
+    AppState,
+};
+
+const BUTTON_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.5);
+const BUTTON_HOVERED_COLOR: Color = Color::srgba(0.2, 0.2, 0.2, 0.7);
+
+const RUN_CODE_MAX_LEN: usize = 32;
+
+#[derive(Component)]
+pub struct Menu;
+
+#[derive(Component, Clone, Copy)]
+pub enum MenuButton {
+    Play(GameMode),
+    Curve,
+    Tuning,
+    Players,
+    PlayCode,
+    /// Race the typed code online, or today's daily challenge
+    Online,
+    CopyLastCode,
+    /// Switch to the next profile
+    Profile,
+    /// Create a profile named after the typed text
+    NewProfile,
+    Leaderboard,
+    Stats,
+    Achievements,
+    MusicVolume,
+    EffectsVolume,
+    CameraEffect(CameraEffect),
+    CameraMode,
+}
+
+/// Menu text refreshed from the settings
+#[derive(Component, Clone, Copy)]
+pub enum MenuLabel {
+    Daily,
+    Curve,
+    Tuning,
+    Players,
+    RunCode,
+    Online,
+    LastCode,
+    Profile,
+    NewProfile,
+    MusicVolume,
+    EffectsVolume,
+    CameraEffect(CameraEffect),
+    CameraMode,
+}
+
+pub fn spawn_menu(mut commands: Commands) {
+    let buttons = [
+        (
+            MenuButton::Play(GameMode::Endless),
+            None,
+            "ENDLESS".to_string(),
+        ),
+        (
+            MenuButton::Play(GameMode::TimeAttack),
+            None,
+            "TIME ATTACK".to_string(),
+        ),
+        (
+            MenuButton::Play(GameMode::Daily),
+            Some(MenuLabel::Daily),
+            String::new(),
+        ),
+        (MenuButton::Curve, Some(MenuLabel::Curve), String::new()),
+        (MenuButton::Tuning, Some(MenuLabel::Tuning), String::new()),
+        (MenuButton::Players, Some(MenuLabel::Players), String::new()),
+        (
+            MenuButton::PlayCode,
+            Some(MenuLabel::RunCode),
+            String::new(),
+        ),
+        (MenuButton::Online, Some(MenuLabel::Online), String::new()),
+        (
+            MenuButton::CopyLastCode,
+            Some(MenuLabel::LastCode),
+            String::new(),
+        ),
+        (MenuButton::Profile, Some(MenuLabel::Profile), String::new()),
+        (
+            MenuButton::NewProfile,
+            Some(MenuLabel::NewProfile),
+            String::new(),
+        ),
+        (MenuButton::Leaderboard, None, "LEADERBOARD".to_string()),
+        (MenuButton::Stats, None, "STATS".to_string()),
+        (MenuButton::Achievements, None, "ACHIEVEMENTS".to_string()),
+        (
+            MenuButton::MusicVolume,
+            Some(MenuLabel::MusicVolume),
+            String::new(),
+        ),
+        (
+            MenuButton::EffectsVolume,
+            Some(MenuLabel::EffectsVolume),
+            String::new(),
+        ),
+        (
+            MenuButton::CameraEffect(CameraEffect::FovKick),
+            Some(MenuLabel::CameraEffect(CameraEffect::FovKick)),
+            String::new(),
+        ),
+        (
+            MenuButton::CameraEffect(CameraEffect::Shake),
+            Some(MenuLabel::CameraEffect(CameraEffect::Shake)),
+            String::new(),
+        ),
+        (
+            MenuButton::CameraEffect(CameraEffect::Dip),
+            Some(MenuLabel::CameraEffect(CameraEffect::Dip)),
+            String::new(),
+        ),
+        (
+            MenuButton::CameraEffect(CameraEffect::Tilt),
+            Some(MenuLabel::CameraEffect(CameraEffect::Tilt)),
+            String::new(),
+        ),
+        (
+            MenuButton::CameraMode,
+            Some(MenuLabel::CameraMode),
+            String::new(),
+        ),
+    ];
+
+    commands
+        .spawn((
+            Menu,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+        ))
+        .with_children(|c| {
+            c.spawn((
+                Text::new("PARKOUR!"),
+                TextFont {
+                    font_size: 60.0,
+                    ..default()
+                },
+            ));
+
+            // Two columns of buttons
+            c.spawn(Node {
+                width: Val::Px(816.0),
+                flex_wrap: FlexWrap::Wrap,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(8.0),
+                column_gap: Val::Px(8.0),
+                ..default()
+            })
+            .with_children(|c| {
+                for (button, label, text) in buttons {
+                    c.spawn((
+                        button,
+                        Button,
+                        Node {
+                            width: Val::Px(400.0),
+                            padding: UiRect::all(Val::Px(6.0)),
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        BackgroundColor(BUTTON_COLOR),
+                    ))
+                    .with_children(|c| {
+                        let mut text = c.spawn((
+                            Text::new(text),
+                            TextFont {
+                                font_size: 24.0,
+                                ..default()
+                            },
+                            TextLayout::new_with_justify(JustifyText::Center),
+                        ));
+
+                        if let Some(label) = label {
+                            text.insert(label);
+                        }
+                    });
+                }
+            });
+        });
+}
+
+pub fn despawn_menu(mut commands: Commands, query: Query<Entity, With<Menu>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn menu_buttons(
+    mut commands: Commands,
+    mut run_settings: ResMut<RunSettings>,
+    mut profiles: ResMut<Profiles>,
+    mut audio_settings: ResMut<AudioSettings>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut input: ResMut<RunCodeInput>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut query: Query<(&Interaction, &MenuButton, &mut BackgroundColor), Changed<Interaction>>,
+) {
+    for (interaction, button, mut background) in &mut query {
+        match interaction {
+            Interaction::Pressed => match *button {
+                MenuButton::Play(mode) => {
+                    run_settings.mode = mode;
+                    run_settings.seed = None;
+                    next_state.set(AppState::Game);
+                }
+                MenuButton::Curve => {
+                    run_settings.curve = next_preset(&DifficultyCurve::ALL, run_settings.curve);
+                }
+                MenuButton::Tuning => {
+                    run_settings.tuning = next_preset(&TuningPreset::ALL, run_settings.tuning);
+                }
+                MenuButton::Players => {
+                    run_settings.players = run_settings.players % MAX_PLAYERS + 1;
+                }
+                MenuButton::PlayCode => input.autostart = true,
+                MenuButton::Online => {
+                    let code = if input.text.is_empty() {
+                        Ok(RunCode {
+                            seed: UtcDate::today().seed(),
+                            mode: GameMode::Daily,
+                            curve: DifficultyCurve::default(),
+                            tuning: TuningPreset::default(),
+                        })
+                    } else {
+                        input.text.parse::<RunCode>()
+                    };
+
+                    match code {
+                        Ok(code) => {
+                            code.apply(&mut run_settings);
+                            // Everyone in the room races alone on their screen, without assists
+                            run_settings.players = 1;
+                            run_settings.online = true;
+                            commands.insert_resource(OnlineRace::new(code.to_string()));
+                            next_state.set(AppState::Game);
+                        }
+                        Err(e) => input.error = Some(e),
+                    }
+                }
+                MenuButton::CopyLastCode => {
+                    if let Some(last) = input.last {
+                        copy_to_clipboard(&last.to_string());
+                    }
+                }
+                MenuButton::Profile => {
+                    profiles.cycle(&mut run_settings);
+                    commands.insert_resource(DailyChallenge::new(profiles.current()));
+                }
+                MenuButton::NewProfile => {
+                    profiles.create(&input.text, &run_settings);
+                    input.text.clear();
+                    input.error = None;
+                    commands.insert_resource(DailyChallenge::new(profiles.current()));
+                }
+                MenuButton::Leaderboard => next_state.set(AppState::Leaderboard),
+                MenuButton::Stats => next_state.set(AppState::Stats),
+                MenuButton::Achievements => next_state.set(AppState::Achievements),
+                MenuButton::MusicVolume => {
+                    audio_settings.music = AudioSettings::next_volume(audio_settings.music);
+                }
+                MenuButton::EffectsVolume => {
+                    audio_settings.effects = AudioSettings::next_volume(audio_settings.effects);
+                }
+                MenuButton::CameraEffect(effect) => camera_settings.cycle(effect),
+                MenuButton::CameraMode => {
+                    camera_settings.mode = next_preset(&CameraMode::ALL, camera_settings.mode);
+                }
+            },
+            Interaction::Hovered => background.0 = BUTTON_HOVERED_COLOR,
+            Interaction::None => background.0 = BUTTON_COLOR,
+        }
+    }
+}
+
+fn next_preset<T: Copy + PartialEq>(all: &[T], current: T) -> T {
+    let index = all.iter().position(|p| *p == current).unwrap_or_default();
+    all[(index + 1) % all.len()]
+}
+
+/// Type or paste a run code, Enter plays it
+pub fn menu_run_code_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut run_settings: ResMut<RunSettings>,
+    mut input: ResMut<RunCodeInput>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let ctrl = keyboard.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        match &event.logical_key {
+            Key::Character(c) if ctrl && c.eq_ignore_ascii_case("v") => {
+                if let Some(pasted) = paste_from_clipboard() {
+                    input.text = pasted.trim().to_string();
+                }
+            }
+            Key::Character(c) if ctrl && c.eq_ignore_ascii_case("c") => {
+                if let Some(last) = input.last {
+                    copy_to_clipboard(&last.to_string());
+                }
+            }
+            Key::Character(c) if !ctrl => {
+                input.text.extend(
+                    c.chars()
+                        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+                        .map(|c| c.to_ascii_uppercase()),
+                );
+            }
+            Key::Backspace => {
+                input.text.pop();
+            }
+            Key::Enter => input.autostart = true,
+            _ => {}
+        }
+
+        input.text.truncate(RUN_CODE_MAX_LEN);
+        input.error = None;
+    }
+
+    if input.autostart {
+        input.autostart = false;
+
+        match input.text.parse::<RunCode>() {
+            Ok(code) => {
+                code.apply(&mut run_settings);
+                next_state.set(AppState::Game);
+            }
+            Err(e) => input.error = Some(e),
+        }
+    }
+}
+
+pub fn update_menu(
+    run_settings: Res<RunSettings>,
+    profiles: Res<Profiles>,
+    audio_settings: Res<AudioSettings>,
+    camera_settings: Res<CameraSettings>,
+    daily: Res<DailyChallenge>,
+    input: Res<RunCodeInput>,
+    mut query: Query<(&mut Text, &MenuLabel)>,
+) {
+    for (mut text, label) in &mut query {
+        let value = match label {
+            MenuLabel::Daily => format!(
+                "DAILY CHALLENGE {}\nBEST: {}",
+                daily.date,
+                daily.best(&run_settings)
+            ),
+            MenuLabel::Curve => format!("DIFFICULTY: {}", run_settings.curve.name()),
+            MenuLabel::Tuning => format!("MOVEMENT: {}", run_settings.tuning.name()),
+            MenuLabel::Players => format!("PLAYERS: {}", run_settings.players),
+            MenuLabel::RunCode => match &input.error {
+                Some(e) => format!("{e}: {}", input.text),
+                None if input.text.is_empty() => "TYPE OR PASTE A RUN CODE".to_string(),
+                None => format!("PLAY {}", input.text),
+            },
+            MenuLabel::Online if input.text.is_empty() => "RACE TODAY'S DAILY ONLINE".to_string(),
+            MenuLabel::Online => format!("RACE {} ONLINE", input.text),
+            MenuLabel::LastCode => match input.last {
+                Some(last) => format!("LAST RUN: {last}\nCLICK OR CTRL+C TO COPY"),
+                None => "PLAY A RUN TO GET ITS CODE".to_string(),
+            },
+            MenuLabel::Profile => format!("PROFILE: {}", profiles.current().name),
+            MenuLabel::NewProfile if input.text.is_empty() => "NEW PROFILE".to_string(),
+            MenuLabel::NewProfile => format!(
+                "NEW PROFILE: {}",
+                input
+                    .text
+                    .chars()
+                    .take(PROFILE_NAME_MAX_LEN)
+                    .collect::<String>()
+            ),
+            MenuLabel::MusicVolume => format!("MUSIC: {:.0}%", audio_settings.music * 100.0),
+            MenuLabel::EffectsVolume => {
+                format!("EFFECTS: {:.0}%", audio_settings.effects * 100.0)
+            }
+            MenuLabel::CameraEffect(effect) => format!(
+                "{}: {:.0}%",
+                effect.name(),
+                camera_settings.level(*effect) * 100.0
+            ),
+            MenuLabel::CameraMode => format!("CAMERA: {}", camera_settings.mode.name()),
+        };
+
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use bevy::{
+    asset::LoadState,
+    audio::{Decodable, Source, Volume},
+    prelude::*,
+};
+use std::{
+    f32::consts::TAU,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    audio::AudioSettings,
+    events::{PlatformLanded, RunStarted, ThemeChanged},
+    game::Game,
+    player::Player,
+    skybox::SKYBOX_TRANSITION_DURATION,
+    theme::ThemeCurrent,
+    AppState,
+};
+
+/// Above what the ear can tell, the filter lets everything through
+const NO_FILTER_CUTOFF: f32 = 18000.0;
+/// Regular jumps keep the music clear, only long flights muffle it
+const AIRBORNE_FILTER_DELAY: f32 = 0.4;
+const FILTER_SPEED: f32 = 6.0;
+/// Volume change per second of a layer following the gameplay
+const LAYER_FADE_SPEED: f32 = 0.5;
+const MILESTONE_POINTS: u32 = 100;
+
+#[derive(Clone, Copy)]
+pub enum LayerDriver {
+    /// `Game::difficulty`
+    Difficulty,
+    /// Forward speed of the first player
+    Speed,
+}
+
+/// Stem mixed over the base track, louder as its driver rises
+pub struct MusicLayer {
+    pub file: &'static str,
+    pub driver: LayerDriver,
+    /// Silent up to this value
+    pub from: f32,
+    /// Full volume from this value
+    pub to: f32,
+}
+
+/// Tracks of a theme, they must all have the same length to loop together
+pub struct ThemeMusic {
+    pub base: &'static str,
+    pub layers: &'static [MusicLayer],
+    /// Played over the music on score milestones
+    pub stinger: &'static str,
+    /// Low-pass cutoff in Hz while the first player is in the air
+    pub airborne_cutoff: f32,
+}
+
+#[derive(Clone)]
+pub struct MusicLoad {
+    pub music: &'static ThemeMusic,
+    tracks: Vec<Handle<AudioSource>>,
+    stinger: Handle<AudioSource>,
+}
+
+impl MusicLoad {
+    pub fn new(music: &'static ThemeMusic, assets_server: &AssetServer) -> Self {
+        let load = |file: &str| assets_server.load(format!("music/{file}"));
+
+        Self {
+            music,
+            // Base first, then the layers
+            tracks: std::iter::once(music.base)
+                .chain(music.layers.iter().map(|layer| layer.file))
+                .map(load)
+                .collect(),
+            stinger: load(music.stinger),
+        }
+    }
+}
+
+/// Track decoded through the low-pass filter shared by the music
+#[derive(Asset, TypePath)]
+pub struct FilteredAudio {
+    source: AudioSource,
+    cutoff: Arc<AtomicU32>,
+}
+
+impl Decodable for FilteredAudio {
+    type DecoderItem = f32;
+    type Decoder = LowPass;
+
+    fn decoder(&self) -> Self::Decoder {
+        let source = self.source.decoder();
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+
+        LowPass {
+            source,
+            cutoff: self.cutoff.clone(),
+            channels,
+            sample_rate,
+            channel: 0,
+            alpha: 1.0,
+            state: vec![0.0; channels.into()],
+        }
+    }
+}
+
+/// One-pole low-pass filter, the cutoff is read once per frame of samples
+pub struct LowPass {
+    source: <AudioSource as Decodable>::Decoder,
+    cutoff: Arc<AtomicU32>,
+    channels: u16,
+    sample_rate: u32,
+    channel: usize,
+    alpha: f32,
+    state: Vec<f32>,
+}
+
+impl Iterator for LowPass {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = f32::from(self.source.next()?) / f32::from(i16::MAX);
+
+        if self.channel == 0 {
+            let cutoff = f32::from_bits(self.cutoff.load(Ordering::Relaxed));
+            self.alpha = if cutoff >= NO_FILTER_CUTOFF {
+                1.0
+            } else {
+                1.0 - (-TAU * cutoff / self.sample_rate as f32).exp()
+            };
+        }
+
+        let filtered = &mut self.state[self.channel];
+        *filtered += self.alpha * (sample - *filtered);
+        let filtered = *filtered;
+
+        self.channel = (self.channel + 1) % usize::from(self.channels);
+
+        Some(filtered)
+    }
+}
+
+impl Source for LowPass {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+/// Gameplay state heard in the music
+#[derive(Resource)]
+pub struct AdaptiveMusic {
+    /// Low-pass cutoff in Hz, as f32 bits, read by the playing tracks
+    cutoff: Arc<AtomicU32>,
+    milestone: u32,
+}
+
+impl Default for AdaptiveMusic {
+    fn default() -> Self {
+        Self {
+            cutoff: Arc::new(AtomicU32::new(NO_FILTER_CUTOFF.to_bits())),
+            milestone: 0,
+        }
+    }
+}
+
+/// A theme's tracks, faded in and out together with the skybox
+#[derive(Component)]
+pub struct Music {
+    load: MusicLoad,
+    fade: Timer,
+    fading_out: bool,
+    playing: bool,
+}
+
+impl Music {
+    fn volume(&self) -> f32 {
+        if self.fading_out {
+            1.0 - self.fade.fraction()
+        } else {
+            self.fade.fraction()
+        }
+    }
+}
+
+/// Base track or layer of a `Music`, as its child
+#[derive(Component)]
+pub struct MusicTrack {
+    layer: Option<usize>,
+    gain: f32,
+}
+
+// The first theme starts right away, the next ones crossfade while the skybox dissolves
+pub fn crossfade_music(
+    trigger: Trigger<ThemeChanged>,
+    mut commands: Commands,
+    theme_current: Res<ThemeCurrent>,
+    mut q_music: Query<&mut Music>,
+) {
+    let duration = if trigger.event().previous.is_some() {
+        SKYBOX_TRANSITION_DURATION
+    } else {
+        0.0
+    };
+
+    for mut music in &mut q_music {
+        // Start from the volume of a track still fading in
+        let mut fade = Timer::from_seconds(duration, TimerMode::Once);
+        fade.set_elapsed(fade.duration().mul_f32(1.0 - music.volume()));
+
+        music.fade = fade;
+        music.fading_out = true;
+    }
+
+    commands.spawn(Music {
+        load: theme_current.theme.music.clone(),
+        fade: Timer::from_seconds(duration, TimerMode::Once),
+        fading_out: false,
+        playing: false,
+    });
+}
+
+// Every track starts on the same frame to stay in sync, missing files are left out
+pub fn start_music(
+    mut commands: Commands,
+    assets_server: Res<AssetServer>,
+    audio_sources: Res<Assets<AudioSource>>,
+    mut filtered_audio: ResMut<Assets<FilteredAudio>>,
+    adaptive_music: Res<AdaptiveMusic>,
+    mut q_music: Query<(Entity, &mut Music)>,
+) {
+    for (entity, mut music) in &mut q_music {
+        let loaded = music.load.tracks.iter().all(|handle| {
+            matches!(
+                assets_server.load_state(handle),
+                LoadState::Loaded | LoadState::Failed(_)
+            )
+        });
+
+        if music.playing || !loaded {
+            continue;
+        }
+
+        music.playing = true;
+
+        for (i, handle) in music.load.tracks.iter().enumerate() {
+            let Some(source) = audio_sources.get(handle) else {
+                continue;
+            };
+
+            let audio = filtered_audio.add(FilteredAudio {
+                source: source.clone(),
+                cutoff: adaptive_music.cutoff.clone(),
+            });
+
+            commands.entity(entity).with_child((
+                MusicTrack {
+                    layer: i.checked_sub(1),
+                    gain: 0.0,
+                },
+                AudioPlayer(audio),
+                PlaybackSettings::LOOP.with_volume(Volume::new(0.0)),
+            ));
+        }
+    }
+}
+
+pub fn update_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    state: Res<State<AppState>>,
+    game: Res<Game>,
+    audio_settings: Res<AudioSettings>,
+    mut q_music: Query<(Entity, &mut Music)>,
+    mut q_tracks: Query<(&Parent, &mut MusicTrack, Option<&AudioSink>)>,
+    q_players: Query<&Player>,
+) {
+    for (entity, mut music) in &mut q_music {
+        music.fade.tick(time.delta());
+
+        if music.fading_out && music.fade.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let in_game = *state.get() == AppState::Game;
+    let speed = q_players
+        .iter()
+        .find(|player| player.id == 0)
+        .map_or(0.0, |player| player.forward_speed);
+
+    for (parent, mut track, sink) in &mut q_tracks {
+        let Ok((_, music)) = q_music.get(parent.get()) else {
+            continue;
+        };
+
+        // Layers stay out of the menu
+        let target = match track.layer.map(|i| &music.load.music.layers[i]) {
+            None => 1.0,
+            Some(_) if !in_game => 0.0,
+            Some(layer) => {
+                let value = match layer.driver {
+                    LayerDriver::Difficulty => game.difficulty(),
+                    LayerDriver::Speed => speed,
+                };
+                ((value - layer.from) / (layer.to - layer.from)).clamp(0.0, 1.0)
+            }
+        };
+
+        // The base track is in from the start
+        let step = if track.layer.is_some() {
+            LAYER_FADE_SPEED * time.delta_secs()
+        } else {
+            1.0
+        };
+        track.gain += (target - track.gain).clamp(-step, step);
+
+        // The sink shows up once the track plays
+        if let Some(sink) = sink {
+            sink.set_volume(audio_settings.music * music.volume() * track.gain);
+        }
+    }
+}
+
+// Muffle the music during long flights
+pub fn update_music_filter(
+    time: Res<Time>,
+    state: Res<State<AppState>>,
+    adaptive_music: Res<AdaptiveMusic>,
+    theme_current: Option<Res<ThemeCurrent>>,
+    q_players: Query<&Player>,
+) {
+    let airborne = *state.get() == AppState::Game
+        && q_players.iter().any(|player| {
+            player.id == 0
+                && player.started
+                && !player.is_done()
+                && player.air_time > AIRBORNE_FILTER_DELAY
+        });
+
+    let target = match theme_current {
+        Some(theme_current) if airborne => theme_current.theme.music.music.airborne_cutoff,
+        _ => NO_FILTER_CUTOFF,
+    };
+
+    // Glide in octaves rather than in Hz, like the ear hears it
+    let cutoff = f32::from_bits(adaptive_music.cutoff.load(Ordering::Relaxed));
+    let t = 1.0 - (-FILTER_SPEED * time.delta_secs()).exp();
+    let cutoff = cutoff * (target / cutoff).powf(t);
+
+    adaptive_music
+        .cutoff
+        .store(cutoff.to_bits(), Ordering::Relaxed);
+}
+
+pub fn music_run_started(trigger: Trigger<RunStarted>, mut adaptive_music: ResMut<AdaptiveMusic>) {
+    if trigger.event().player == 0 {
+        adaptive_music.milestone = 0;
+    }
+}
+
+pub fn music_milestone(
+    trigger: Trigger<PlatformLanded>,
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    theme_current: Res<ThemeCurrent>,
+    mut adaptive_music: ResMut<AdaptiveMusic>,
+) {
+    let landed = trigger.event();
+    let milestone = landed.points / MILESTONE_POINTS;
+
+    if landed.player != 0 || milestone <= adaptive_music.milestone {
+        return;
+    }
+
+    adaptive_music.milestone = milestone;
+
+    if audio_settings.music > 0.0 {
+        commands.spawn((
+            AudioPlayer(theme_current.theme.music.stinger.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(audio_settings.music)),
+        ));
+    }
+}
@@ -0,0 +1,432 @@
+use bevy::{pbr::NotShadowCaster, prelude::*, utils::HashMap};
+
+use crate::{
+    course::Course,
+    game::RunSettings,
+    player::Player,
+    protocol::{
+        decode, encode, ClientMessage, RacerState, ServerMessage, DEFAULT_RELAY_PORT,
+        PROTOCOL_VERSION,
+    },
+};
+
+const STATE_SEND_INTERVAL: f32 = 0.1;
+const GHOST_LERP_SPEED: f32 = 10.0;
+
+/// Relay address and racer name, from `--relay URL` on the command line or `?relay=URL` on wasm
+#[derive(Resource)]
+pub struct OnlineSettings {
+    pub relay_url: String,
+    pub name: String,
+}
+
+impl Default for OnlineSettings {
+    fn default() -> Self {
+        Self {
+            relay_url: startup_relay_url()
+                .unwrap_or_else(|| format!("ws://127.0.0.1:{DEFAULT_RELAY_PORT}")),
+            name: format!("RACER-{:04}", rand::random::<u16>() % 10000),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum OnlineStatus {
+    Connecting,
+    Joined(u32),
+    Failed(String),
+}
+
+pub struct RemoteRacer {
+    pub name: String,
+    pub state: RacerState,
+}
+
+/// Online race in the room of a run code, the other racers are shown as ghosts
+#[derive(Resource)]
+pub struct OnlineRace {
+    pub room: String,
+    pub status: OnlineStatus,
+    pub racers: HashMap<u32, RemoteRacer>,
+    send: Timer,
+}
+
+impl OnlineRace {
+    pub fn new(room: String) -> Self {
+        Self {
+            room,
+            status: OnlineStatus::Connecting,
+            racers: HashMap::default(),
+            send: Timer::from_seconds(STATE_SEND_INTERVAL, TimerMode::Repeating),
+        }
+    }
+
+    /// Rank of a racer at `distance` among everyone in the room, from 1
+    pub fn rank(&self, distance: f32) -> usize {
+        1 + self
+            .racers
+            .values()
+            .filter(|racer| racer.state.distance > distance)
+            .count()
+    }
+
+    /// Closest racer ahead of `distance`
+    pub fn ahead(&self, distance: f32) -> Option<&RemoteRacer> {
+        self.racers
+            .values()
+            .filter(|racer| racer.state.distance > distance)
+            .min_by(|a, b| a.state.distance.total_cmp(&b.state.distance))
+    }
+}
+
+#[derive(Component)]
+pub struct Ghost {
+    id: u32,
+    target: Vec3,
+}
+
+#[derive(Resource)]
+pub struct GhostAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+pub fn load_ghost_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(GhostAssets {
+        mesh: meshes.add(Sphere::new(0.8)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.7, 0.9, 1.0, 0.4),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+/// Connect to the relay when an online race starts
+pub fn join_online_race(
+    mut commands: Commands,
+    online_race: Option<Res<OnlineRace>>,
+    settings: Res<OnlineSettings>,
+) {
+    let Some(online_race) = online_race else {
+        return;
+    };
+
+    let url = settings.relay_url.clone();
+    let join = ClientMessage::Join {
+        version: PROTOCOL_VERSION,
+        room: online_race.room.clone(),
+        name: settings.name.clone(),
+    };
+
+    // The connection isn't Send on wasm
+    commands.queue(move |world: &mut World| {
+        let mut connection = Connection::connect(&url);
+        if let Some(text) = encode(&join) {
+            connection.send(text);
+        }
+        world.insert_non_send_resource(connection);
+    });
+}
+
+pub fn leave_online_race(
+    mut commands: Commands,
+    mut run_settings: ResMut<RunSettings>,
+    q_ghosts: Query<Entity, With<Ghost>>,
+) {
+    run_settings.online = false;
+
+    for entity in &q_ghosts {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands.remove_resource::<OnlineRace>();
+    commands.queue(|world: &mut World| {
+        world.remove_non_send_resource::<Connection>();
+    });
+}
+
+pub fn update_online_race(
+    mut commands: Commands,
+    time: Res<Time>,
+    course: Res<Course>,
+    ghost_assets: Res<GhostAssets>,
+    mut online_race: ResMut<OnlineRace>,
+    connection: Option<NonSendMut<Connection>>,
+    q_players: Query<(&Player, &Transform)>,
+    mut q_ghosts: Query<(Entity, &mut Ghost)>,
+) {
+    // Connected at the end of the first frame
+    let Some(mut connection) = connection else {
+        return;
+    };
+
+    for message in connection.poll() {
+        match message {
+            ServerMessage::Joined { id } => online_race.status = OnlineStatus::Joined(id),
+            ServerMessage::Rejected { reason } => online_race.status = OnlineStatus::Failed(reason),
+            ServerMessage::RacerJoined { id, name } => {
+                online_race.racers.insert(
+                    id,
+                    RemoteRacer {
+                        name,
+                        state: RacerState::default(),
+                    },
+                );
+            }
+            ServerMessage::RacerLeft { id } => {
+                online_race.racers.remove(&id);
+
+                for (entity, ghost) in &q_ghosts {
+                    if ghost.id == id {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+            }
+            ServerMessage::RacerState { id, state } => {
+                let (x, y, z) = state.position;
+                let target = Vec3::new(x, y, z);
+
+                if let Some((_, mut ghost)) = q_ghosts.iter_mut().find(|(_, ghost)| ghost.id == id)
+                {
+                    ghost.target = target;
+                } else {
+                    commands.spawn((
+                        Ghost { id, target },
+                        Mesh3d(ghost_assets.mesh.clone()),
+                        MeshMaterial3d(ghost_assets.material.clone()),
+                        Transform::from_translation(target),
+                        NotShadowCaster,
+                    ));
+                }
+
+                if let Some(racer) = online_race.racers.get_mut(&id) {
+                    racer.state = state;
+                }
+            }
+        }
+    }
+
+    // Stream our own state
+    if !matches!(online_race.status, OnlineStatus::Joined(_))
+        || !online_race.send.tick(time.delta()).just_finished()
+    {
+        return;
+    }
+
+    let Some((player, transform)) = q_players.iter().find(|(player, _)| player.id == 0) else {
+        return;
+    };
+
+    let position = transform.translation;
+    let state = RacerState {
+        position: (position.x, position.y, position.z),
+        points: player.points,
+        distance: course.distance_travelled(player.id, position),
+        finish_time: player.finish_time,
+    };
+
+    if let Some(text) = encode(&ClientMessage::State(state)) {
+        connection.send(text);
+    }
+}
+
+pub fn update_ghosts(time: Res<Time>, mut q_ghosts: Query<(&mut Transform, &Ghost)>) {
+    for (mut transform, ghost) in &mut q_ghosts {
+        // Smooth the updates received a few times per second
+        transform.translation = transform.translation.lerp(
+            ghost.target,
+            (GHOST_LERP_SPEED * time.delta_secs()).min(1.0),
+        );
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn startup_relay_url() -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--relay");
+    args.next()?;
+    args.next()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn startup_relay_url() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|param| param.strip_prefix("relay="))
+        .map(str::to_string)
+}
+
+/// WebSocket to the relay, running on a thread on native
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Connection {
+    outgoing: std::sync::mpsc::Sender<String>,
+    incoming: std::sync::mpsc::Receiver<ServerMessage>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Connection {
+    fn connect(url: &str) -> Self {
+        use std::{io::ErrorKind, sync::mpsc, time::Duration};
+        use tungstenite::{stream::MaybeTlsStream, Error, Message};
+
+        let (outgoing, outgoing_receiver) = mpsc::channel::<String>();
+        let (incoming_sender, incoming) = mpsc::channel();
+        let url = url.to_string();
+
+        std::thread::spawn(move || {
+            let fail = |reason: String| {
+                let _ = incoming_sender.send(ServerMessage::Rejected { reason });
+            };
+
+            let mut socket = match tungstenite::connect(&url) {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    warn!("can't connect to relay {url}: {e}");
+                    return fail("CAN'T REACH RELAY".to_string());
+                }
+            };
+
+            if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+                let _ = stream.set_read_timeout(Some(Duration::from_millis(10)));
+            }
+
+            loop {
+                match socket.read() {
+                    Ok(Message::Text(text)) => {
+                        if let Some(message) = decode(&text) {
+                            let _ = incoming_sender.send(message);
+                        }
+                    }
+                    Ok(Message::Close(_)) => return fail("DISCONNECTED".to_string()),
+                    Ok(_) => {}
+                    Err(Error::Io(e))
+                        if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                    Err(_) => return fail("DISCONNECTED".to_string()),
+                }
+
+                loop {
+                    match outgoing_receiver.try_recv() {
+                        Ok(text) => {
+                            if socket.send(Message::text(text)).is_err() {
+                                return fail("DISCONNECTED".to_string());
+                            }
+                        }
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        // The race is over
+                        Err(mpsc::TryRecvError::Disconnected) => {
+                            let _ = socket.close(None);
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { outgoing, incoming }
+    }
+
+    fn send(&mut self, text: String) {
+        let _ = self.outgoing.send(text);
+    }
+
+    fn poll(&mut self) -> Vec<ServerMessage> {
+        self.incoming.try_iter().collect()
+    }
+}
+
+/// WebSocket to the relay, messages are queued until it opens
+#[cfg(target_arch = "wasm32")]
+pub struct Connection {
+    socket: Option<web_sys::WebSocket>,
+    pending: Vec<String>,
+    incoming: std::rc::Rc<std::cell::RefCell<Vec<ServerMessage>>>,
+    _on_message: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MessageEvent)>,
+    _on_close: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::Event)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Connection {
+    fn connect(url: &str) -> Self {
+        use wasm_bindgen::{closure::Closure, JsCast};
+
+        let incoming = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+
+        let on_message = {
+            let incoming = incoming.clone();
+            Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |event: web_sys::MessageEvent| {
+                if let Some(message) = event.data().as_string().and_then(|text| decode(&text)) {
+                    incoming.borrow_mut().push(message);
+                }
+            })
+        };
+
+        let on_close = {
+            let incoming = incoming.clone();
+            Closure::<dyn FnMut(web_sys::Event)>::new(move |_| {
+                incoming.borrow_mut().push(ServerMessage::Rejected {
+                    reason: "DISCONNECTED".to_string(),
+                });
+            })
+        };
+
+        let socket = web_sys::WebSocket::new(url).ok();
+
+        if let Some(socket) = &socket {
+            socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        } else {
+            incoming.borrow_mut().push(ServerMessage::Rejected {
+                reason: "CAN'T REACH RELAY".to_string(),
+            });
+        }
+
+        Self {
+            socket,
+            pending: Vec::new(),
+            incoming,
+            _on_message: on_message,
+            _on_close: on_close,
+        }
+    }
+
+    fn send(&mut self, text: String) {
+        self.pending.push(text);
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+
+        if socket.ready_state() == web_sys::WebSocket::OPEN {
+            for text in self.pending.drain(..) {
+                let _ = socket.send_with_str(&text);
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Vec<ServerMessage> {
+        self.flush();
+        self.incoming.borrow_mut().drain(..).collect()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(socket) = &self.socket {
+            socket.set_onmessage(None);
+            socket.set_onclose(None);
+            let _ = socket.close();
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+use crate::AppState;
+
+/// Whether the gameplay runs, only while in game
+#[derive(SubStates, Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+#[source(AppState = AppState::Game)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+    /// Free camera over the frozen course
+    Photo,
+}
+
+#[derive(Component)]
+pub struct PauseScreen;
+
+// Gameplay systems only run in `PauseState::Running`, the virtual time stops everything else
+pub fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+pub fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn start_just_pressed(keyboard_input: &ButtonInput<KeyCode>, q_gamepads: &Query<&Gamepad>) -> bool {
+    keyboard_input.just_pressed(KeyCode::Escape)
+        || q_gamepads
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(GamepadButton::Start))
+}
+
+pub fn pause_game(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    q_gamepads: Query<&Gamepad>,
+    mut next_pause: ResMut<NextState<PauseState>>,
+) {
+    if start_just_pressed(&keyboard_input, &q_gamepads) {
+        next_pause.set(PauseState::Paused);
+    }
+}
+
+pub fn spawn_pause_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            PauseScreen,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        ))
+        .with_child((
+            Text::new("PAUSED\n\nESCAPE / START: RESUME\nF / Y: PHOTO MODE\nM / SELECT: MENU"),
+            TextFont {
+                font_size: 40.0,
+                ..default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+        ));
+}
+
+pub fn despawn_pause_screen(mut commands: Commands, query: Query<Entity, With<PauseScreen>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn pause_menu(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    q_gamepads: Query<&Gamepad>,
+    mut next_pause: ResMut<NextState<PauseState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let any_gamepad = |button| {
+        q_gamepads
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(button))
+    };
+
+    if start_just_pressed(&keyboard_input, &q_gamepads) {
+        next_pause.set(PauseState::Running);
+    } else if keyboard_input.just_pressed(KeyCode::KeyF) || any_gamepad(GamepadButton::North) {
+        next_pause.set(PauseState::Photo);
+    } else if keyboard_input.just_pressed(KeyCode::KeyM) || any_gamepad(GamepadButton::Select) {
+        next_state.set(AppState::Menu);
+    }
+}
@@ -0,0 +1,506 @@
+use bevy::{
+    core_pipeline::dof::{DepthOfField, DepthOfFieldMode},
+    input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll},
+    prelude::*,
+    render::view::{
+        screenshot::{save_to_disk, Screenshot, ScreenshotCaptured},
+        ColorGrading, ColorGradingGlobal, ColorGradingSection, RenderLayers,
+    },
+};
+use std::{f32::consts::PI, path::PathBuf};
+
+use crate::{
+    camera::{CameraMode, CameraSettings, CAMERA_FOV},
+    daily::unix_time_secs,
+    pause::PauseState,
+    player::{Player, PlayerCamera, PlayerModel, MAX_PLAYERS},
+};
+
+const MOVE_SPEED: f32 = 8.0;
+/// Holding shift
+const FAST_MOVE_SPEED: f32 = 25.0;
+const MOUSE_LOOK_SENSITIVITY: f32 = 0.003;
+const STICK_LOOK_SPEED: f32 = 2.0;
+const STICK_DEAD_ZONE: f32 = 0.15;
+const MAX_PITCH: f32 = PI / 2.0 - 0.01;
+
+const MIN_FOV: f32 = PI / 9.0;
+const MAX_FOV: f32 = PI * 2.0 / 3.0;
+/// Radians per second on the keys, per line on the mouse wheel
+const FOV_SPEED: f32 = 0.8;
+const FOV_SCROLL_STEP: f32 = 0.05;
+
+const MIN_FOCUS: f32 = 0.5;
+const MAX_FOCUS: f32 = 200.0;
+/// Focus distance change per second, as a factor
+const FOCUS_SPEED: f32 = 2.0;
+
+/// In stops, both ways
+const MAX_EXPOSURE: f32 = 3.0;
+const EXPOSURE_SPEED: f32 = 1.5;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum PhotoFilter {
+    #[default]
+    Natural,
+    Mono,
+    Warm,
+    Cool,
+    Vivid,
+}
+
+impl PhotoFilter {
+    pub const ALL: [Self; 5] = [
+        Self::Natural,
+        Self::Mono,
+        Self::Warm,
+        Self::Cool,
+        Self::Vivid,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Natural => "NATURAL",
+            Self::Mono => "MONO",
+            Self::Warm => "WARM",
+            Self::Cool => "COOL",
+            Self::Vivid => "VIVID",
+        }
+    }
+
+    fn color_grading(self, exposure: f32) -> ColorGrading {
+        let global = ColorGradingGlobal {
+            exposure,
+            ..default()
+        };
+
+        let (global, section) = match self {
+            Self::Natural => (global, ColorGradingSection::default()),
+            Self::Mono => (
+                ColorGradingGlobal {
+                    post_saturation: 0.0,
+                    ..global
+                },
+                ColorGradingSection {
+                    contrast: 1.1,
+                    ..default()
+                },
+            ),
+            Self::Warm => (
+                ColorGradingGlobal {
+                    temperature: 0.4,
+                    ..global
+                },
+                ColorGradingSection::default(),
+            ),
+            Self::Cool => (
+                ColorGradingGlobal {
+                    temperature: -0.4,
+                    ..global
+                },
+                ColorGradingSection::default(),
+            ),
+            Self::Vivid => (
+                ColorGradingGlobal {
+                    post_saturation: 1.4,
+                    ..global
+                },
+                ColorGradingSection {
+                    contrast: 1.15,
+                    ..default()
+                },
+            ),
+        };
+
+        ColorGrading::with_identical_sections(global, section)
+    }
+}
+
+/// Lens of the photo camera, kept from one photo session to the next
+#[derive(Resource)]
+pub struct PhotoSettings {
+    fov: f32,
+    depth_of_field: bool,
+    focus: f32,
+    /// Exposure compensation, in stops
+    exposure: f32,
+    filter: PhotoFilter,
+}
+
+impl Default for PhotoSettings {
+    fn default() -> Self {
+        Self {
+            fov: CAMERA_FOV,
+            depth_of_field: false,
+            focus: 10.0,
+            exposure: 0.0,
+            filter: PhotoFilter::default(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PhotoCamera;
+
+/// Controls and settings shown over the shot, hidden while capturing
+#[derive(Component)]
+pub struct PhotoUi;
+
+#[derive(Component)]
+pub struct PhotoText;
+
+/// Last photo saved, or why it wasn't
+#[derive(Resource, Default)]
+pub struct PhotoStatus(String);
+
+// The free camera starts from the first player's view, the player cameras stop rendering meanwhile
+pub fn enter_photo_mode(
+    mut commands: Commands,
+    photo_settings: Res<PhotoSettings>,
+    mut q_cameras: Query<(&mut Camera, &GlobalTransform, &PlayerCamera)>,
+    q_players: Query<&Player>,
+    q_models: Query<(&Parent, &Children), With<PlayerModel>>,
+    mut q_parts: Query<&mut RenderLayers>,
+) {
+    let mut transform = Transform::default();
+
+    for (mut camera, global_transform, player_camera) in &mut q_cameras {
+        camera.is_active = false;
+
+        if player_camera.0 == 0 {
+            transform = global_transform.compute_transform().with_scale(Vec3::ONE);
+        }
+    }
+
+    // Show the first player to the photo camera, which sees what their camera sees
+    set_first_player_model_layers(&q_players, &q_models, &mut q_parts, true);
+
+    let camera = commands
+        .spawn((
+            PhotoCamera,
+            Camera3d::default(),
+            Camera {
+                order: isize::try_from(MAX_PLAYERS).unwrap_or_default(),
+                hdr: true,
+                ..default()
+            },
+            Projection::Perspective(PerspectiveProjection {
+                fov: photo_settings.fov,
+                ..default()
+            }),
+            photo_settings.filter.color_grading(photo_settings.exposure),
+            transform,
+            RenderLayers::from_layers(&[0, 1]),
+        ))
+        .id();
+
+    commands.insert_resource(PhotoStatus::default());
+
+    commands
+        .spawn((
+            PhotoUi,
+            TargetCamera(camera),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::End,
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+        ))
+        .with_child((
+            PhotoText,
+            Text::default(),
+            TextFont {
+                font_size: 20.0,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        ));
+}
+
+pub fn exit_photo_mode(
+    mut commands: Commands,
+    camera_settings: Res<CameraSettings>,
+    mut q_cameras: Query<&mut Camera, With<PlayerCamera>>,
+    q_photo: Query<Entity, Or<(With<PhotoCamera>, With<PhotoUi>)>>,
+    q_players: Query<&Player>,
+    q_models: Query<(&Parent, &Children), With<PlayerModel>>,
+    mut q_parts: Query<&mut RenderLayers>,
+) {
+    for entity in &q_photo {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for mut camera in &mut q_cameras {
+        camera.is_active = true;
+    }
+
+    set_first_player_model_layers(
+        &q_players,
+        &q_models,
+        &mut q_parts,
+        camera_settings.mode == CameraMode::Chase,
+    );
+}
+
+fn set_first_player_model_layers(
+    q_players: &Query<&Player>,
+    q_models: &Query<(&Parent, &Children), With<PlayerModel>>,
+    q_parts: &mut Query<&mut RenderLayers>,
+    seen_by_self: bool,
+) {
+    for (parent, children) in q_models {
+        if q_players
+            .get(parent.get())
+            .is_ok_and(|player| player.id == 0)
+        {
+            let mut parts = q_parts.iter_many_mut(children);
+            while let Some(mut layers) = parts.fetch_next() {
+                *layers = PlayerModel::layers(0, seen_by_self);
+            }
+        }
+    }
+}
+
+// Gameplay time is frozen, the camera moves in real time
+pub fn fly_photo_camera(
+    time: Res<Time<Real>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    q_gamepads: Query<&Gamepad>,
+    mut q_camera: Query<&mut Transform, With<PhotoCamera>>,
+) {
+    let Ok(mut transform) = q_camera.get_single_mut() else {
+        return;
+    };
+
+    let dt = time.delta_secs();
+    let axis = |negative, positive| {
+        f32::from(i8::from(keyboard.pressed(positive)) - i8::from(keyboard.pressed(negative)))
+    };
+
+    // Right, up, backwards
+    let mut movement = Vec3::new(
+        axis(KeyCode::KeyA, KeyCode::KeyD),
+        axis(KeyCode::KeyQ, KeyCode::KeyE),
+        axis(KeyCode::KeyW, KeyCode::KeyS),
+    );
+    let mut look = Vec2::ZERO;
+
+    // Looking around while holding the right button, the left one is the jump
+    if mouse.pressed(MouseButton::Right) {
+        look -= mouse_motion.delta * MOUSE_LOOK_SENSITIVITY;
+    }
+
+    for gamepad in &q_gamepads {
+        let left = gamepad.left_stick();
+        if left.length() > STICK_DEAD_ZONE {
+            movement += Vec3::new(left.x, 0.0, -left.y);
+        }
+
+        movement.y += gamepad
+            .get(GamepadButton::RightTrigger2)
+            .unwrap_or_default()
+            - gamepad.get(GamepadButton::LeftTrigger2).unwrap_or_default();
+
+        let right = gamepad.right_stick();
+        if right.length() > STICK_DEAD_ZONE {
+            look += Vec2::new(-right.x, right.y) * STICK_LOOK_SPEED * dt;
+        }
+    }
+
+    let speed = if keyboard.pressed(KeyCode::ShiftLeft) {
+        FAST_MOVE_SPEED
+    } else {
+        MOVE_SPEED
+    };
+
+    let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+    transform.rotation = Quat::from_euler(
+        EulerRot::YXZ,
+        yaw + look.x,
+        (pitch + look.y).clamp(-MAX_PITCH, MAX_PITCH),
+        0.0,
+    );
+
+    let movement = transform.rotation * movement.clamp_length_max(1.0);
+    transform.translation += movement * speed * dt;
+}
+
+pub fn adjust_photo_settings(
+    time: Res<Time<Real>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_scroll: Res<AccumulatedMouseScroll>,
+    q_gamepads: Query<&Gamepad>,
+    mut photo_settings: ResMut<PhotoSettings>,
+) {
+    let dt = time.delta_secs();
+    let axis = |negative, positive| {
+        f32::from(i8::from(keyboard.pressed(positive)) - i8::from(keyboard.pressed(negative)))
+    };
+    let any_gamepad = |button| {
+        q_gamepads
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(button))
+    };
+
+    // Only touch the settings on a change, they're applied to the camera when changed
+    let fov = (photo_settings.fov + axis(KeyCode::Digit2, KeyCode::Digit1) * FOV_SPEED * dt
+        - mouse_scroll.delta.y * FOV_SCROLL_STEP)
+        .clamp(MIN_FOV, MAX_FOV);
+    if (fov - photo_settings.fov).abs() > f32::EPSILON {
+        photo_settings.fov = fov;
+    }
+
+    let focus = axis(KeyCode::Digit3, KeyCode::Digit4);
+    if focus != 0.0 {
+        photo_settings.focus =
+            (photo_settings.focus * FOCUS_SPEED.powf(focus * dt)).clamp(MIN_FOCUS, MAX_FOCUS);
+    }
+
+    let exposure = axis(KeyCode::Digit5, KeyCode::Digit6);
+    if exposure != 0.0 {
+        photo_settings.exposure = (photo_settings.exposure + exposure * EXPOSURE_SPEED * dt)
+            .clamp(-MAX_EXPOSURE, MAX_EXPOSURE);
+    }
+
+    if keyboard.just_pressed(KeyCode::KeyF) || any_gamepad(GamepadButton::West) {
+        photo_settings.depth_of_field = !photo_settings.depth_of_field;
+    }
+
+    if keyboard.just_pressed(KeyCode::KeyG) || any_gamepad(GamepadButton::North) {
+        let index = PhotoFilter::ALL
+            .iter()
+            .position(|filter| *filter == photo_settings.filter)
+            .unwrap_or_default();
+        photo_settings.filter = PhotoFilter::ALL[(index + 1) % PhotoFilter::ALL.len()];
+    }
+
+    if keyboard.just_pressed(KeyCode::KeyR) {
+        *photo_settings = PhotoSettings::default();
+    }
+}
+
+pub fn apply_photo_settings(
+    mut commands: Commands,
+    photo_settings: Res<PhotoSettings>,
+    photo_status: Res<PhotoStatus>,
+    mut q_camera: Query<(Entity, &mut Projection, &mut ColorGrading), With<PhotoCamera>>,
+    mut q_text: Query<&mut Text, With<PhotoText>>,
+) {
+    if !photo_settings.is_changed() && !photo_status.is_changed() {
+        return;
+    }
+
+    for (entity, mut projection, mut color_grading) in &mut q_camera {
+        if let Projection::Perspective(perspective) = &mut *projection {
+            perspective.fov = photo_settings.fov;
+        }
+
+        *color_grading = photo_settings.filter.color_grading(photo_settings.exposure);
+
+        // Gaussian, the bokeh mode isn't supported on WebGL 2
+        if photo_settings.depth_of_field {
+            commands.entity(entity).insert(DepthOfField {
+                mode: DepthOfFieldMode::Gaussian,
+                focal_distance: photo_settings.focus,
+                ..default()
+            });
+        } else {
+            commands.entity(entity).remove::<DepthOfField>();
+        }
+    }
+
+    let depth_of_field = if photo_settings.depth_of_field {
+        format!("{:.1}m", photo_settings.focus)
+    } else {
+        "OFF".to_string()
+    };
+
+    for mut text in &mut q_text {
+        text.0 = format!(
+            "PHOTO MODE {}\n\
+            WASD QE / STICKS TRIGGERS: MOVE, SHIFT: FASTER, RIGHT MOUSE: LOOK\n\
+            1 2 / WHEEL: FOV {:.0}\n\
+            F / X: DEPTH OF FIELD {depth_of_field}, 3 4: FOCUS\n\
+            5 6: EXPOSURE {:+.1}\n\
+            G / Y: FILTER {}\n\
+            R: RESET, ENTER / A: CAPTURE, ESCAPE / B: BACK",
+            photo_status.0,
+            photo_settings.fov.to_degrees(),
+            photo_settings.exposure,
+            photo_settings.filter.name(),
+        );
+    }
+}
+
+// The overlay is hidden on the captured frame, and shown again once the photo is taken
+pub fn photo_mode_input(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    q_gamepads: Query<&Gamepad>,
+    mut next_pause: ResMut<NextState<PauseState>>,
+    mut count: Local<u32>,
+    mut q_ui: Query<&mut Visibility, With<PhotoUi>>,
+) {
+    let any_gamepad = |button| {
+        q_gamepads
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(button))
+    };
+
+    if keyboard.just_pressed(KeyCode::Escape) || any_gamepad(GamepadButton::East) {
+        next_pause.set(PauseState::Paused);
+        return;
+    }
+
+    if !(keyboard.just_pressed(KeyCode::Enter) || any_gamepad(GamepadButton::South)) {
+        return;
+    }
+
+    *count += 1;
+    let name = format!("parkour-{}-{}.png", unix_time_secs(), *count);
+
+    let Some(path) = photo_path(&name) else {
+        commands.insert_resource(PhotoStatus("- CAN'T SAVE PHOTOS".to_string()));
+        return;
+    };
+
+    for mut visibility in &mut q_ui {
+        *visibility = Visibility::Hidden;
+    }
+
+    commands
+        .spawn(Screenshot::primary_window())
+        .observe(save_to_disk(path))
+        .observe(
+            move |_trigger: Trigger<ScreenshotCaptured>,
+                  mut photo_status: ResMut<PhotoStatus>,
+                  mut q_ui: Query<&mut Visibility, With<PhotoUi>>| {
+                photo_status.0 = format!("- SAVED {name}");
+
+                for mut visibility in &mut q_ui {
+                    *visibility = Visibility::Inherited;
+                }
+            },
+        );
+}
+
+/// Photos go to the game's folder on native, the browser downloads them on wasm
+#[cfg(not(target_arch = "wasm32"))]
+fn photo_path(name: &str) -> Option<PathBuf> {
+    let dir = crate::storage::data_dir().join("photos");
+
+    std::fs::create_dir_all(&dir)
+        .inspect_err(|e| warn!("can't create the photos folder {}: {e}", dir.display()))
+        .ok()?;
+
+    Some(dir.join(name))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn photo_path(name: &str) -> Option<PathBuf> {
+    Some(PathBuf::from(name))
+}
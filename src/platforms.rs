@@ -1,29 +1,37 @@
 use avian3d::prelude::*;
 use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use bevy::utils::HashMap;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 use std::f32::consts::PI;
 
 use crate::{
+    biomes::BiomeProgress,
+    branches::{Fork, BRANCH_CHANCE},
+    course::Course,
+    events::{PlatformLanded, PlatformMissed},
     game::Game,
+    game::RunSettings,
+    player::PlayerSet,
+    practice::{spawn_checkpoint, CheckpointAssets, CHECKPOINT_INTERVAL},
+    segments::{
+        spawn_hazards, Hazard, SegmentAssets, SegmentInProgress, SegmentPrefab, SEGMENT_CHANCE,
+    },
     theme::{ThemeChange, ThemeCurrent, THEME_CHANGE_CHANCE},
+    time_attack::{spawn_goal, GoalAssets},
+    vfx::{Crumbling, SpawnIn},
     ChangeThemeRandom,
 };
 
-const PLATFORM_SPACING_MIN: f32 = 5.0;
-const PLATFORM_SPACING_MAX: f32 = 9.0;
-
 const DIRECTION_BIAS_HORIZONTAL_CHANCE: f64 = 0.1;
 const DIRECTION_BIAS_VERTICAL_CHANCE: f64 = 0.1;
 
-const VERTICAL_VARIATION_UP: f32 = 3.0;
-const VERTICAL_VARIATION_DOWN: f32 = 8.0;
-const HORIZONTAL_VARIATION: f32 = 8.0;
-
-const MOVING_PLATFORM_CHANCE_MIN: f64 = 0.1;
-const MOVING_PLATFORM_CHANCE_MAX: f64 = 0.5;
-
+/// Position of the platform in the course, in spawn order
 #[derive(Component)]
-pub struct Platform;
+pub struct Platform {
+    pub index: u32,
+}
 
 #[derive(Component)]
 pub struct MovingPlatform {
@@ -33,109 +41,521 @@ pub struct MovingPlatform {
 }
 
 pub const TOUCHED_PLATFORM_TTL: f32 = 10.0;
+const SKIPPED_PLATFORM_TTL: f32 = 2.0;
 
+/// Crumbling platform, despawned when the timer ends
 #[derive(Component)]
 pub struct Touched(pub Timer);
 
-#[derive(Component)]
-pub struct Hovered;
+/// Players that landed on the platform
+#[derive(Component, Default)]
+pub struct TouchedBy(pub PlayerSet);
 
-#[derive(Resource, Default)]
+#[derive(Resource, Clone)]
 pub struct PlatformGeneration {
+    pub seed: u64,
+    /// Only source of randomness for the course layout, the same seed always generates the same platforms.
+    /// `StdRng` may change its output between rand versions, `ChaCha8Rng` doesn't
+    pub rng: ChaCha8Rng,
+    pub next_platform_index: u32,
+    /// Platforms along the course so far, parallel branch platforms count once.
+    /// The generation difficulty follows it, so it doesn't depend on how the players score
+    pub steps: u32,
     pub next_platform_position: Vec3,
     pub direction_bias_horizontal: f64,
     pub direction_bias_vertical: f64,
+    pub biome: BiomeProgress,
+    /// Predecessors of the next platform, several right after branches merge
+    pub last_platforms: Vec<u32>,
+    pub segment: Option<SegmentInProgress>,
+    pub fork: Option<Fork>,
+    pub last_checkpoint: Option<u32>,
+    /// Steps along the course to the goal platform on finite courses, nothing is spawned after it
+    pub goal: Option<u32>,
+    pub finished: bool,
+}
+
+impl PlatformGeneration {
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            next_platform_index: 0,
+            steps: 0,
+            next_platform_position: Vec3::ZERO,
+            direction_bias_horizontal: 0.0,
+            direction_bias_vertical: 0.0,
+            biome: BiomeProgress::default(),
+            last_platforms: Vec::new(),
+            segment: None,
+            fork: None,
+            last_checkpoint: None,
+            goal: None,
+            finished: false,
+        }
+    }
+}
+
+impl Default for PlatformGeneration {
+    fn default() -> Self {
+        Self::from_seed(rand::random())
+    }
 }
 
 #[derive(Event)]
 pub struct SpawnPlatform;
 
+/// How platform collision shapes are built from their meshes
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum PlatformColliderMode {
+    /// Exact, but the most expensive to build and to collide against
+    #[default]
+    Trimesh,
+    /// A single convex shape wrapping the whole mesh
+    ConvexHull,
+    /// Several convex shapes approximating the mesh
+    ConvexDecomposition,
+}
+
+impl PlatformColliderMode {
+    fn next(self) -> Self {
+        match self {
+            Self::Trimesh => Self::ConvexHull,
+            Self::ConvexHull => Self::ConvexDecomposition,
+            Self::ConvexDecomposition => Self::Trimesh,
+        }
+    }
+
+    fn collider_from_mesh(self, mesh: &Mesh) -> Option<Collider> {
+        match self {
+            Self::Trimesh => Collider::trimesh_from_mesh(mesh),
+            Self::ConvexHull => Collider::convex_hull_from_mesh(mesh),
+            Self::ConvexDecomposition => Collider::convex_decomposition_from_mesh(mesh),
+        }
+    }
+
+    fn constructor(self) -> ColliderConstructor {
+        match self {
+            Self::Trimesh => ColliderConstructor::TrimeshFromMesh,
+            Self::ConvexHull => ColliderConstructor::ConvexHullFromMesh,
+            Self::ConvexDecomposition => ColliderConstructor::ConvexDecompositionFromMesh,
+        }
+    }
+}
+
+/// Collider shapes built once per platform scene, shared by every spawned platform.
+/// They are built in the background, convex decompositions can take a while
+#[derive(Resource, Default)]
+pub struct PlatformColliders {
+    pub mode: PlatformColliderMode,
+    built_mode: PlatformColliderMode,
+    colliders: HashMap<AssetId<Scene>, Collider>,
+    building: HashMap<AssetId<Scene>, Task<Option<Collider>>>,
+}
+
+impl PlatformColliders {
+    /// Start building the colliders of the scenes that don't have one for the current mode yet
+    pub fn build(
+        &mut self,
+        handles: &[Handle<Scene>],
+        scenes: &Assets<Scene>,
+        meshes: &Assets<Mesh>,
+    ) {
+        if self.mode != self.built_mode {
+            self.colliders.clear();
+            // Dropping the tasks cancels them
+            self.building.clear();
+            self.built_mode = self.mode;
+        }
+
+        for handle in handles {
+            if self.colliders.contains_key(&handle.id()) || self.building.contains_key(&handle.id())
+            {
+                continue;
+            }
+
+            if let Some(scene) = scenes.get(handle) {
+                let scene_meshes = scene_meshes(&scene.world, meshes);
+                let mode = self.mode;
+
+                self.building.insert(
+                    handle.id(),
+                    AsyncComputeTaskPool::get()
+                        .spawn(async move { scene_collider(scene_meshes, mode) }),
+                );
+            }
+        }
+    }
+
+    /// Every scene of `handles` has its collider, or failed to get one
+    pub fn is_built(&self, handles: &[Handle<Scene>]) -> bool {
+        handles
+            .iter()
+            .all(|handle| !self.building.contains_key(&handle.id()))
+    }
+}
+
+pub fn finish_platform_colliders(mut platform_colliders: ResMut<PlatformColliders>) {
+    let mut built = Vec::new();
+
+    platform_colliders
+        .building
+        .retain(|id, task| match block_on(future::poll_once(task)) {
+            Some(collider) => {
+                built.push((*id, collider));
+                false
+            }
+            None => true,
+        });
+
+    for (id, collider) in built {
+        if let Some(collider) = collider {
+            platform_colliders.colliders.insert(id, collider);
+        }
+    }
+}
+
+// Meshes of a scene relative to the scene root, copied out so the shapes can be built off the main thread
+fn scene_meshes(world: &World, meshes: &Assets<Mesh>) -> Vec<(Transform, Mesh)> {
+    world
+        .iter_entities()
+        .filter_map(|entity| {
+            let mesh = meshes.get(&entity.get::<Mesh3d>()?.0)?;
+
+            let mut transform = entity.get::<Transform>().copied().unwrap_or_default();
+            let mut parent = entity.get::<Parent>();
+
+            while let Some(parent_entity) = parent.map(|p| world.entity(p.get())) {
+                if let Some(parent_transform) = parent_entity.get::<Transform>() {
+                    transform = *parent_transform * transform;
+                }
+                parent = parent_entity.get::<Parent>();
+            }
+
+            Some((transform, mesh.clone()))
+        })
+        .collect()
+}
+
+// Merge every mesh of a scene into one compound collider
+fn scene_collider(meshes: Vec<(Transform, Mesh)>, mode: PlatformColliderMode) -> Option<Collider> {
+    let shapes = meshes
+        .into_iter()
+        .filter_map(|(transform, mesh)| {
+            // compound colliders only carry position & rotation, bake the scale into the mesh
+            let collider = mode.collider_from_mesh(&mesh.scaled_by(transform.scale))?;
+
+            Some((transform.translation, transform.rotation, collider))
+        })
+        .collect::<Vec<_>>();
+
+    (!shapes.is_empty()).then(|| Collider::compound(shapes))
+}
+
+/// Move the player along the course and keep the same amount of platforms ahead of the leader,
+/// even if some were skipped
+pub fn advance_course(
+    trigger: Trigger<PlatformLanded>,
+    mut commands: Commands,
+    mut course: ResMut<Course>,
+) {
+    let landed = trigger.event();
+
+    for _ in 0..course.reach(landed.player, landed.index) {
+        commands.trigger(SpawnPlatform);
+    }
+
+    if landed.missed > 0 {
+        commands.trigger_targets(
+            PlatformMissed {
+                player: landed.player,
+                count: landed.missed,
+            },
+            trigger.entity(),
+        );
+    }
+}
+
 pub fn spawn_platform(
     _trigger: Trigger<SpawnPlatform>,
     mut commands: Commands,
-    mut platform_gen: ResMut<PlatformGeneration>,
-    game: Res<Game>,
+    platform_gen: ResMut<PlatformGeneration>,
+    mut course: ResMut<Course>,
+    platform_colliders: Res<PlatformColliders>,
+    segment_assets: Res<SegmentAssets>,
+    segment_prefabs: Res<Assets<SegmentPrefab>>,
+    checkpoint_assets: Res<CheckpointAssets>,
+    goal_assets: Res<GoalAssets>,
+    run_settings: Res<RunSettings>,
     theme_current: Res<ThemeCurrent>,
     theme_change: Option<Res<ThemeChange>>,
 ) {
-    let mut rng = rand::thread_rng();
-
-    // Small chance to change current theme if not already changing
-    if theme_change.is_none() && rng.gen_bool(THEME_CHANGE_CHANCE) {
+    // Small chance to change current theme if not already changing. Themes are only cosmetic,
+    // so the roll doesn't draw from the course generation rng
+    if theme_change.is_none() && rand::thread_rng().gen_bool(THEME_CHANGE_CHANCE) {
         commands.trigger(ChangeThemeRandom);
     }
 
-    // Platform mesh
-    let size = (1.0 - game.difficulty()) * rng.gen_range(0.8..1.2);
+    if platform_gen.finished {
+        return;
+    }
+
+    // Generation state before this platform, to regenerate the course from a checkpoint
+    let snapshot = run_settings.practicing().then(|| platform_gen.clone());
 
-    let handle = theme_current
-        .theme
-        .platforms
-        .choose(&mut rng)
-        .unwrap()
-        .clone();
+    // The course only depends on the generation rng, cosmetic choices use another one
+    let platform_gen = platform_gen.into_inner();
+    let rng = &mut platform_gen.rng;
+    let difficulty = run_settings.curve.course_difficulty(platform_gen.steps);
 
-    // Small chance to update direction bias
-    if rng.gen_bool(DIRECTION_BIAS_HORIZONTAL_CHANCE) {
-        platform_gen.direction_bias_horizontal = rng.gen_range(0.0..1.0);
+    let mut spawner = PlatformSpawner {
+        commands: &mut commands,
+        course: &mut course,
+        platform_colliders: &platform_colliders,
+        theme_current: &theme_current,
+    };
+
+    // Small chance to fork the course if not already in a segment
+    if platform_gen.segment.is_none() && platform_gen.fork.is_none() {
+        if let Some(start) = platform_gen
+            .last_platforms
+            .first()
+            .and_then(|index| spawner.course.get(*index))
+            .map(|platform| platform.position)
+        {
+            if rng.gen_bool(BRANCH_CHANCE) {
+                platform_gen.fork = Some(Fork::plan(rng, start, &platform_gen.last_platforms));
+            }
+        }
     }
 
-    if rng.gen_bool(DIRECTION_BIAS_VERTICAL_CHANCE) {
-        platform_gen.direction_bias_vertical = rng.gen_range(0.0..1.0);
+    // Fork: one more platform on every branch still going
+    if let Some(mut fork) = platform_gen.fork.take() {
+        for branch in &mut fork.branches {
+            if let Some(position) = branch.positions.pop_front() {
+                let next_position = branch.positions.front().copied().unwrap_or(fork.merge);
+
+                let transform = Transform::from_translation(position)
+                    .with_scale(Vec3::splat((1.0 - difficulty) * branch.size))
+                    .looking_at(next_position.with_y(position.y), Vec3::Y);
+
+                let index = platform_gen.next_platform_index;
+                platform_gen.next_platform_index += 1;
+
+                spawner.spawn(index, transform, branch.points, &branch.last_platforms);
+
+                branch.last_platforms = vec![index];
+            }
+        }
+        platform_gen.steps += 1;
+
+        if fork.is_finished() {
+            // Branches merge on the next platform
+            platform_gen.next_platform_position = fork.merge;
+            platform_gen.last_platforms = fork
+                .branches
+                .into_iter()
+                .flat_map(|branch| branch.last_platforms)
+                .collect();
+        } else {
+            platform_gen.fork = Some(fork);
+        }
+
+        return;
     }
 
-    // Position
-    let next_platform_z = if rng.gen_bool(platform_gen.direction_bias_horizontal) {
-        rng.gen_range(-HORIZONTAL_VARIATION..0.0)
-    } else {
-        rng.gen_range(0.0..HORIZONTAL_VARIATION)
-    };
+    // Small chance to insert a hand-designed segment if not already in one
+    if platform_gen.segment.is_none() && rng.gen_bool(SEGMENT_CHANCE) {
+        platform_gen.segment = segment_assets.pick(
+            rng,
+            &segment_prefabs,
+            difficulty,
+            platform_gen.next_platform_position,
+        );
+    }
+
+    let position;
+    let transform;
+    let moving;
+    let mut hazards = Vec::new();
+
+    if let Some(queued) = platform_gen
+        .segment
+        .as_mut()
+        .and_then(|segment| segment.platforms.pop_front())
+    {
+        // Segment platform, placed as designed
+        let segment = platform_gen.segment.as_ref().unwrap();
+        let next_platform_position = segment
+            .platforms
+            .front()
+            .map_or(segment.exit, |platform| platform.position);
+
+        if segment.platforms.is_empty() {
+            platform_gen.segment = None;
+        }
 
-    let next_platform_y = if rng.gen_bool(platform_gen.direction_bias_vertical) {
-        rng.gen_range(-VERTICAL_VARIATION_DOWN..0.0)
+        platform_gen.next_platform_position = next_platform_position;
+
+        position = queued.position;
+        transform = Transform::from_translation(position)
+            .with_scale(Vec3::splat(queued.scale))
+            .looking_at(next_platform_position.with_y(position.y), Vec3::Y);
+        moving = queued.moving;
+        hazards = queued.hazards;
     } else {
-        rng.gen_range(0.0..VERTICAL_VARIATION_UP)
-    };
+        // Procedural platform
+        let size = (1.0 - difficulty) * rng.gen_range(0.8..1.2);
 
-    let position = platform_gen.next_platform_position;
-    let mut next_platform_spacing = rng.gen_range(PLATFORM_SPACING_MIN..PLATFORM_SPACING_MAX);
+        // Small chance to update direction bias
+        if rng.gen_bool(DIRECTION_BIAS_HORIZONTAL_CHANCE) {
+            platform_gen.direction_bias_horizontal = rng.gen_range(0.0..1.0);
+        }
 
-    // bigger gap if we are going down
-    if position.y > next_platform_y + 4.0 {
-        next_platform_spacing *= 1.0 + rng.gen_range(0.0..0.2);
-    }
+        if rng.gen_bool(DIRECTION_BIAS_VERTICAL_CHANCE) {
+            platform_gen.direction_bias_vertical = rng.gen_range(0.0..1.0);
+        }
+
+        // Current biome rules
+        let params = platform_gen.biome.params(
+            platform_gen.direction_bias_vertical,
+            platform_gen.direction_bias_horizontal,
+        );
+
+        platform_gen.biome.advance(rng);
+
+        // Position
+        let next_platform_z = if rng.gen_bool(params.horizontal_bias) {
+            rng.gen_range(-params.horizontal..0.0)
+        } else {
+            rng.gen_range(0.0..params.horizontal)
+        };
+
+        let next_platform_y = if rng.gen_bool(params.vertical_bias) {
+            rng.gen_range(-params.vertical_down..0.0)
+        } else {
+            rng.gen_range(0.0..params.vertical_up)
+        };
+
+        position = platform_gen.next_platform_position;
+        let mut next_platform_spacing = rng.gen_range(params.spacing_min..params.spacing_max);
 
-    // Set next platform position
-    platform_gen.next_platform_position +=
-        Vec3::new(rng.gen_range(4.0..8.0), next_platform_y, next_platform_z).normalize()
+        // bigger gap if we are going down
+        if position.y > next_platform_y + 4.0 {
+            next_platform_spacing *= 1.0 + rng.gen_range(0.0..0.2);
+        }
+
+        // Set next platform position
+        platform_gen.next_platform_position += Vec3::new(
+            rng.gen_range(params.forward_min..params.forward_max),
+            next_platform_y,
+            next_platform_z,
+        )
+        .normalize()
             * next_platform_spacing;
 
-    let mut transform = Transform::from_translation(position)
-        .with_scale(Vec3::splat(size))
-        .looking_at(platform_gen.next_platform_position, Vec3::Y);
+        let mut random_transform = Transform::from_translation(position)
+            .with_scale(Vec3::splat(size))
+            .looking_at(platform_gen.next_platform_position, Vec3::Y);
+
+        random_transform.rotate_y(rng.gen_range(0.0..PI * 2.0));
+        transform = random_transform;
+
+        // Chance to be a moving platform
+        let moving_platform_chance =
+            (f64::from(difficulty) + params.moving_chance_min).min(params.moving_chance_max);
 
-    transform.rotate_y(rng.gen_range(0.0..PI * 2.0));
+        moving = rng.gen_bool(moving_platform_chance);
+    }
 
     // Spawn platform
-    let mut c = commands.spawn((
-        Platform,
-        SceneRoot(handle),
-        transform,
-        ColliderConstructorHierarchy::new(ColliderConstructor::TrimeshFromMesh),
-        RigidBody::Static,
-    ));
-
-    // Chance to be a moving platform
-    let moving_platform_chance =
-        (game.difficulty() as f64 + MOVING_PLATFORM_CHANCE_MIN).min(MOVING_PLATFORM_CHANCE_MAX);
-
-    if rng.gen_bool(moving_platform_chance) {
-        c.insert(MovingPlatform {
+    let index = platform_gen.next_platform_index;
+    platform_gen.next_platform_index += 1;
+    platform_gen.steps += 1;
+
+    let entity = spawner.spawn(index, transform, 1, &platform_gen.last_platforms);
+    platform_gen.last_platforms = vec![index];
+
+    if platform_gen
+        .goal
+        .is_some_and(|goal| platform_gen.steps >= goal)
+    {
+        platform_gen.finished = true;
+        spawn_goal(&mut commands, &goal_assets, entity);
+    }
+
+    // Practice checkpoint every few platforms
+    if let Some(snapshot) = snapshot {
+        if platform_gen
+            .last_checkpoint
+            .is_none_or(|last| index >= last + CHECKPOINT_INTERVAL)
+        {
+            platform_gen.last_checkpoint = Some(index);
+            spawn_checkpoint(&mut commands, &checkpoint_assets, entity, snapshot);
+        }
+    }
+
+    if moving {
+        commands.entity(entity).insert(MovingPlatform {
             progress: rng.gen_range(-1.0..1.0),
             going_negative: rng.gen_bool(0.5),
             z: position.z,
         });
     }
+
+    spawn_hazards(&mut commands, &segment_assets, index, &hazards);
+}
+
+struct PlatformSpawner<'a, 'w, 's> {
+    commands: &'a mut Commands<'w, 's>,
+    course: &'a mut Course,
+    platform_colliders: &'a PlatformColliders,
+    theme_current: &'a ThemeCurrent,
+}
+
+impl PlatformSpawner<'_, '_, '_> {
+    fn spawn(
+        &mut self,
+        index: u32,
+        transform: Transform,
+        points: u32,
+        predecessors: &[u32],
+    ) -> Entity {
+        // Platform mesh
+        let handle = self
+            .theme_current
+            .theme
+            .platforms
+            .choose(&mut rand::thread_rng())
+            .unwrap()
+            .clone();
+
+        let collider = self.platform_colliders.colliders.get(&handle.id()).cloned();
+
+        let mut c = self.commands.spawn((
+            Platform { index },
+            TouchedBy::default(),
+            SceneRoot(handle),
+            transform.with_scale(transform.scale * SpawnIn::START_SCALE),
+            SpawnIn::new(transform.scale),
+            RigidBody::Static,
+        ));
+
+        // Reuse the prebuilt collider, scaled with the platform. Fall back to building it from the scene
+        if let Some(collider) = collider {
+            c.insert(collider);
+        } else {
+            c.insert(ColliderConstructorHierarchy::new(
+                self.platform_colliders.mode.constructor(),
+            ));
+        }
+
+        let entity = c.id();
+        self.course
+            .push(index, entity, transform.translation, points, predecessors);
+
+        entity
+    }
 }
 
 pub fn update_moving_platforms(
@@ -164,14 +584,67 @@ pub fn update_moving_platforms(
     }
 }
 
+// Platforms that can't be reached anymore were skipped or on another branch, retire them like touched ones
+pub fn retire_skipped_platforms(
+    mut commands: Commands,
+    course: Res<Course>,
+    query: Query<(Entity, &Platform), Without<Touched>>,
+) {
+    for (entity, platform) in &query {
+        if !course.contains(platform.index) {
+            commands.entity(entity).insert(Touched(Timer::from_seconds(
+                SKIPPED_PLATFORM_TTL,
+                TimerMode::Once,
+            )));
+        }
+    }
+}
+
+// Hazards are left behind once their platform can't be reached anymore
+pub fn delete_passed_hazards(
+    mut commands: Commands,
+    course: Res<Course>,
+    query: Query<(Entity, &Hazard)>,
+) {
+    for (entity, hazard) in &query {
+        if !course.contains(hazard.platform_index) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Touched platforms crumble away once their time is up
 pub fn delete_touched_platforms(
     time: Res<Time>,
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Touched)>,
+    mut query: Query<(Entity, &Transform, &mut Touched), Without<Crumbling>>,
+    q_children: Query<&Children>,
 ) {
-    for (entity, mut touched) in &mut query {
+    for (entity, transform, mut touched) in &mut query {
         if touched.0.tick(time.delta()).finished() {
-            commands.entity(entity).despawn_recursive();
+            // Gone for the players as soon as it starts crumbling, colliders built from the scene included
+            commands
+                .entity(entity)
+                .remove::<(Collider, ColliderConstructorHierarchy)>()
+                .insert(Crumbling::new(transform.scale));
+
+            for descendant in q_children.iter_descendants(entity) {
+                commands.entity(descendant).remove::<Collider>();
+            }
         }
     }
 }
+
+pub fn cycle_platform_collider_mode(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    theme_current: Res<ThemeCurrent>,
+    scenes: Res<Assets<Scene>>,
+    meshes: Res<Assets<Mesh>>,
+    mut platform_colliders: ResMut<PlatformColliders>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        platform_colliders.mode = platform_colliders.mode.next();
+        platform_colliders.build(&theme_current.theme.platforms, &scenes, &meshes);
+        info!("platform collider mode: {:?}", platform_colliders.mode);
+    }
+}
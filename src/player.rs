@@ -1,18 +1,37 @@
 use avian3d::prelude::*;
 use bevy::{
+    ecs::system::SystemParam,
+    input::mouse::AccumulatedMouseMotion,
     pbr::{NotShadowCaster, NotShadowReceiver},
     prelude::*,
+    render::view::RenderLayers,
 };
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 
 use crate::{
-    game::Game,
-    platforms::{Hovered, Platform, Touched, TOUCHED_PLATFORM_TTL},
-    skybox::{generate_skybox_mesh, SkyboxCustom},
-    PlatformGeneration, SpawnPlatform,
+    camera::{CameraEffects, CameraMode, CameraRig, CameraSettings, CAMERA_FOV},
+    course::{Course, CoursePlatform},
+    events::{DeathCause, JumpBoosted, Jumped, Landed, PlatformLanded, PlayerDied, RunStarted},
+    game::{Game, RunSettings},
+    platforms::{MovingPlatform, Platform, Touched, TouchedBy, TOUCHED_PLATFORM_TTL},
+    rewind::RewindBuffer,
+    segments::Hazard,
+    skybox::{generate_skybox_mesh, SkyboxCustom, SkyboxCustomMaterial},
+    PlatformGeneration,
 };
 
 pub const SPAWN_POINT: Vec3 = Vec3::new(-5.0, 5.0, 0.0);
+const SPAWN_SPACING: f32 = 2.5;
+
+pub const MAX_PLAYERS: usize = 4;
+
+const PLAYER_COLORS: [Color; MAX_PLAYERS] = [
+    Color::srgb(1.0, 0.3, 0.3),
+    Color::srgb(0.3, 0.5, 1.0),
+    Color::srgb(0.3, 1.0, 0.4),
+    Color::srgb(1.0, 0.9, 0.2),
+];
 
 const GRAVITY: f32 = -9.81;
 
@@ -23,92 +42,541 @@ const JUMP_BOOST_DURATION: f32 = 0.6;
 const JUMP_BOOST_MIN_TIME: f32 = 0.15;
 const JUMP_BOOST_SPEED: f32 = 10.0;
 
+/// Shorter hops off the ground, like the ray flickering on a platform edge, aren't landings
+const LANDING_MIN_AIR_TIME: f32 = 0.1;
+
 const SPEED: f32 = 5.0;
 const DIRECTION_LERP: f32 = 0.9;
 
-const CAMERA_ROTATION_SPEED: f32 = 2.0;
+/// Rate of the exponential decay towards the look-ahead point
+const CAMERA_TRACKING_SPEED: f32 = 3.0;
+/// Radians per second
+const CAMERA_MAX_YAW_RATE: f32 = 2.5;
+const CAMERA_MAX_PITCH_RATE: f32 = 1.0;
+const CAMERA_MAX_PITCH: f32 = 0.8;
+/// Distance to the next platform from which the camera starts looking past it
+const LOOK_AHEAD_DISTANCE: f32 = 12.0;
+/// How far towards the platform after the next one the camera ends up looking
+const LOOK_AHEAD: f32 = 0.6;
+
+/// Free-look turn in radians, per pixel of mouse motion
+const MOUSE_LOOK_SENSITIVITY: f32 = 0.003;
+/// Free-look turn in radians per second, stick fully pushed
+const STICK_LOOK_SPEED: f32 = 3.0;
+const STICK_DEAD_ZONE: f32 = 0.15;
+
+/// Movement feel, scaling gravity, jump and speed
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TuningPreset {
+    #[default]
+    Standard,
+    Floaty,
+    Snappy,
+}
 
-#[derive(Component)]
+impl TuningPreset {
+    pub const ALL: [Self; 3] = [Self::Standard, Self::Floaty, Self::Snappy];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Standard => "STANDARD",
+            Self::Floaty => "FLOATY",
+            Self::Snappy => "SNAPPY",
+        }
+    }
+
+    fn gravity(self) -> f32 {
+        match self {
+            Self::Standard => GRAVITY,
+            Self::Floaty => GRAVITY * 0.75,
+            Self::Snappy => GRAVITY * 1.3,
+        }
+    }
+
+    fn jump(self) -> f32 {
+        match self {
+            Self::Standard => JUMP,
+            Self::Floaty => JUMP * 0.9,
+            Self::Snappy => JUMP * 1.15,
+        }
+    }
+
+    fn speed(self) -> f32 {
+        match self {
+            Self::Standard | Self::Floaty => SPEED,
+            Self::Snappy => SPEED * 1.1,
+        }
+    }
+}
+
+struct PlayerKeys {
+    jump: KeyCode,
+    left: KeyCode,
+    right: KeyCode,
+}
+
+const PLAYER_KEYS: [PlayerKeys; MAX_PLAYERS] = [
+    PlayerKeys {
+        jump: KeyCode::Space,
+        left: KeyCode::KeyA,
+        right: KeyCode::KeyD,
+    },
+    PlayerKeys {
+        jump: KeyCode::ArrowUp,
+        left: KeyCode::ArrowLeft,
+        right: KeyCode::ArrowRight,
+    },
+    PlayerKeys {
+        jump: KeyCode::KeyI,
+        left: KeyCode::KeyJ,
+        right: KeyCode::KeyL,
+    },
+    PlayerKeys {
+        jump: KeyCode::Numpad8,
+        left: KeyCode::Numpad4,
+        right: KeyCode::Numpad6,
+    },
+];
+
+/// Set of local players, by id
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PlayerSet(u8);
+
+impl PlayerSet {
+    pub fn insert(&mut self, player: usize) {
+        self.0 |= 1 << player;
+    }
+
+    pub fn contains(self, player: usize) -> bool {
+        self.0 & (1 << player) != 0
+    }
+
+    pub fn contains_all(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+#[derive(Component, Clone)]
 pub struct Player {
+    /// Local player number, from 0
+    pub id: usize,
+    pub points: u32,
+    pub started: bool,
+    /// Fell during a race
+    pub out: bool,
+    /// Time when the goal of a finite course was reached
+    pub finish_time: Option<f32>,
+    /// Time since the player last stood on a platform, without the coyote time
+    pub air_time: f32,
+    /// Horizontal speed towards the next platform, rising with the difficulty
+    pub forward_speed: f32,
+    /// What makes the player fall, a plain fall otherwise
+    death_cause: Option<DeathCause>,
     coyote_time: Timer,
     jump_pressed: bool,
     jump_boost_duration: Timer,
     last_direction_2d: Vec2,
 }
 
-pub fn spawn_player(mut commands: Commands, mut mesh_assets: ResMut<Assets<Mesh>>) {
+impl Player {
+    fn new(id: usize) -> Self {
+        Self {
+            id,
+            points: 0,
+            started: false,
+            out: false,
+            finish_time: None,
+            air_time: 0.0,
+            forward_speed: 0.0,
+            death_cause: None,
+            coyote_time: Timer::from_seconds(COYOTE_TIME, TimerMode::Once),
+            jump_pressed: false,
+            jump_boost_duration: Timer::from_seconds(
+                JUMP_BOOST_DURATION + JUMP_BOOST_MIN_TIME,
+                TimerMode::Once,
+            ),
+            last_direction_2d: Vec2::ZERO,
+        }
+    }
+
+    pub fn restart(&mut self, points: u32) {
+        *self = Self {
+            points,
+            ..Self::new(self.id)
+        };
+    }
+
+    pub fn set_death_cause(&mut self, cause: DeathCause) {
+        self.death_cause = Some(cause);
+    }
+
+    /// Going up faster, jump held after the jump
+    pub fn is_boosting(&self) -> bool {
+        self.jump_pressed
+            && !self.jump_boost_duration.finished()
+            && self.jump_boost_duration.elapsed_secs() > JUMP_BOOST_MIN_TIME
+    }
+
+    /// Not racing anymore, out or at the goal
+    pub fn is_done(&self) -> bool {
+        self.out || self.finish_time.is_some()
+    }
+
+    pub fn spawn_point(id: usize) -> Vec3 {
+        SPAWN_POINT + Vec3::Z * id as f32 * SPAWN_SPACING
+    }
+}
+
+/// Camera of a local player, the first one also renders the menus
+#[derive(Component)]
+pub struct PlayerCamera(pub usize);
+
+/// Where a local player looks, parent of their camera
+#[derive(Component)]
+pub struct CameraPivot(pub usize);
+
+/// Body seen by the other players, and by the player itself with the chase camera
+#[derive(Component)]
+pub struct PlayerModel;
+
+impl PlayerModel {
+    /// Layers of every player's camera, without the player's own one in first person
+    pub fn layers(id: usize, seen_by_self: bool) -> RenderLayers {
+        (0..MAX_PLAYERS)
+            .filter(|other| *other != id || seen_by_self)
+            .map(|other| other + 1)
+            .collect()
+    }
+}
+
+/// Controls of the local players: their keys, then gamepads in connection order.
+/// The first player also jumps with the mouse and touch screen, looks around with the mouse,
+/// and steers with the arrows when alone
+#[derive(SystemParam)]
+pub struct PlayerInput<'w, 's> {
+    keyboard: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    mouse_motion: Res<'w, AccumulatedMouseMotion>,
+    touches: Res<'w, Touches>,
+    run_settings: Res<'w, RunSettings>,
+    gamepads: Query<'w, 's, (Entity, &'static Gamepad)>,
+}
+
+impl PlayerInput<'_, '_> {
+    fn gamepad(&self, player: usize) -> Option<&Gamepad> {
+        let mut gamepads: Vec<_> = self.gamepads.iter().collect();
+        gamepads.sort_by_key(|(entity, _)| *entity);
+        gamepads.get(player).map(|(_, gamepad)| *gamepad)
+    }
+
+    pub fn jump_just_pressed(&self, player: usize) -> bool {
+        self.keyboard.just_pressed(PLAYER_KEYS[player].jump)
+            || (player == 0
+                && (self.mouse.just_pressed(MouseButton::Left)
+                    || self
+                        .touches
+                        .iter_just_pressed()
+                        .any(|touch| touch.id() == 0)))
+            || self
+                .gamepad(player)
+                .is_some_and(|gamepad| gamepad.just_pressed(GamepadButton::South))
+    }
+
+    pub fn jump_just_released(&self, player: usize) -> bool {
+        self.keyboard.just_released(PLAYER_KEYS[player].jump)
+            || (player == 0
+                && (self.mouse.just_released(MouseButton::Left)
+                    || self
+                        .touches
+                        .iter_just_released()
+                        .any(|touch| touch.id() == 0)))
+            || self
+                .gamepad(player)
+                .is_some_and(|gamepad| gamepad.just_released(GamepadButton::South))
+    }
+
+    /// -1.0 when steering left, 1.0 when steering right
+    pub fn steer(&self, player: usize) -> f32 {
+        let keys = &PLAYER_KEYS[player];
+        let solo = self.run_settings.players == 1;
+        let gamepad = self.gamepad(player);
+        let stick = gamepad
+            .and_then(|gamepad| gamepad.get(GamepadAxis::LeftStickX))
+            .unwrap_or_default();
+
+        let left = self.keyboard.pressed(keys.left)
+            || (solo && self.keyboard.pressed(KeyCode::ArrowLeft))
+            || gamepad.is_some_and(|gamepad| gamepad.pressed(GamepadButton::DPadLeft))
+            || stick < -0.5;
+
+        let right = self.keyboard.pressed(keys.right)
+            || (solo && self.keyboard.pressed(KeyCode::ArrowRight))
+            || gamepad.is_some_and(|gamepad| gamepad.pressed(GamepadButton::DPadRight))
+            || stick > 0.5;
+
+        f32::from(i8::from(right) - i8::from(left))
+    }
+
+    /// Free-look turn in radians over `delta_secs`, yaw to the left then pitch upwards
+    pub fn look(&self, player: usize, delta_secs: f32) -> Vec2 {
+        let mouse = if player == 0 {
+            self.mouse_motion.delta * MOUSE_LOOK_SENSITIVITY
+        } else {
+            Vec2::ZERO
+        };
+
+        let stick = self
+            .gamepad(player)
+            .map(Gamepad::right_stick)
+            .filter(|stick| stick.length() > STICK_DEAD_ZONE)
+            .unwrap_or_default()
+            * STICK_LOOK_SPEED
+            * delta_secs;
+
+        // The mouse goes down the screen, the stick up
+        Vec2::new(-mouse.x - stick.x, stick.y - mouse.y)
+    }
+}
+
+pub fn spawn_player(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    spawn_local_player(&mut commands, &mut meshes, &mut materials, 0, None);
+}
+
+/// Spawn a player with its camera, returns the camera.
+/// Each player sees its own skybox, and the others' bodies
+pub fn spawn_local_player(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    id: usize,
+    skybox_material: Option<MeshMaterial3d<SkyboxCustomMaterial>>,
+) -> Entity {
+    let own_layer = id + 1;
+
+    let mut camera = Entity::PLACEHOLDER;
+
     commands
         .spawn((
-            Player {
-                coyote_time: Timer::from_seconds(COYOTE_TIME, TimerMode::Once),
-                jump_pressed: false,
-                jump_boost_duration: Timer::from_seconds(
-                    JUMP_BOOST_DURATION + JUMP_BOOST_MIN_TIME,
-                    TimerMode::Once,
-                ),
-                last_direction_2d: Vec2::ZERO,
-            },
+            Player::new(id),
             RayCaster::new(Vec3::ZERO, -Dir3::Y).with_max_hits(1),
             RigidBody::Kinematic,
             Collider::sphere(1.0),
-            Transform::from_translation(SPAWN_POINT),
+            Transform::from_translation(Player::spawn_point(id)),
             Visibility::default(),
         ))
         .with_children(|c| {
+            // The camera effects move the camera around the pivot
             c.spawn((
-                Camera3d::default(),
-                Projection::Perspective(PerspectiveProjection {
-                    fov: PI / 2.0,
-                    ..default()
-                }),
+                CameraPivot(id),
                 Transform::from_translation(Vec3::Y).looking_at(Vec3::X, Vec3::Y),
-            ));
-
-            c.spawn((
+            ))
+            .with_children(|c| {
+                let mut camera_commands = c.spawn((
+                    PlayerCamera(id),
+                    CameraEffects::default(),
+                    CameraRig::default(),
+                    Camera3d::default(),
+                    Camera {
+                        order: isize::try_from(id).unwrap_or_default(),
+                        ..default()
+                    },
+                    Projection::Perspective(PerspectiveProjection {
+                        fov: CAMERA_FOV,
+                        ..default()
+                    }),
+                    RenderLayers::from_layers(&[0, own_layer]),
+                ));
+                if id == 0 {
+                    camera_commands.insert(IsDefaultUiCamera);
+                }
+                camera = camera_commands.id();
+            });
+
+            let mut skybox = c.spawn((
                 SkyboxCustom,
-                Mesh3d(mesh_assets.add(generate_skybox_mesh())),
+                Mesh3d(meshes.add(generate_skybox_mesh())),
                 NotShadowCaster,
                 NotShadowReceiver,
+                RenderLayers::layer(own_layer),
             ));
+            if let Some(skybox_material) = skybox_material {
+                skybox.insert(skybox_material);
+            }
+
+            // Render layers aren't inherited, each part has them
+            c.spawn((PlayerModel, Transform::default(), Visibility::default()))
+                .with_children(|c| {
+                    c.spawn((
+                        Mesh3d(meshes.add(Capsule3d::new(0.45, 0.9))),
+                        MeshMaterial3d(materials.add(StandardMaterial {
+                            base_color: PLAYER_COLORS[id],
+                            unlit: true,
+                            ..default()
+                        })),
+                        NotShadowCaster,
+                        PlayerModel::layers(id, false),
+                    ));
+
+                    // Visor, showing where the player heads
+                    c.spawn((
+                        Mesh3d(meshes.add(Cuboid::new(0.6, 0.2, 0.2))),
+                        MeshMaterial3d(materials.add(StandardMaterial {
+                            base_color: Color::srgb(0.1, 0.1, 0.15),
+                            unlit: true,
+                            ..default()
+                        })),
+                        Transform::from_xyz(0.0, 0.45, -0.4),
+                        NotShadowCaster,
+                        PlayerModel::layers(id, false),
+                    ));
+                });
         });
+
+    camera
+}
+
+// Face the movement, and show the body to its own camera in third person
+pub fn update_player_models(
+    camera_settings: Res<CameraSettings>,
+    q_players: Query<(&Player, &LinearVelocity)>,
+    mut q_models: Query<(&Parent, &Children, &mut Transform), With<PlayerModel>>,
+    mut q_parts: Query<&mut RenderLayers>,
+) {
+    for (parent, children, mut transform) in &mut q_models {
+        let Ok((player, velocity)) = q_players.get(parent.get()) else {
+            continue;
+        };
+
+        let heading = Vec3::new(velocity.x, 0.0, velocity.z);
+        if heading.length_squared() > 0.01 {
+            transform.look_to(heading, Vec3::Y);
+        }
+
+        let layers = PlayerModel::layers(player.id, camera_settings.mode == CameraMode::Chase);
+
+        for child in children {
+            if let Ok(mut part_layers) = q_parts.get_mut(*child) {
+                if *part_layers != layers {
+                    *part_layers = layers.clone();
+                }
+            }
+        }
+    }
 }
 
 pub fn player_movement(
+    mut commands: Commands,
     time: Res<Time>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mouse: Res<ButtonInput<MouseButton>>,
-    touches: Res<Touches>,
+    input: PlayerInput,
     mut game: ResMut<Game>,
+    run_settings: Res<RunSettings>,
+    course: Res<Course>,
     platform_gen: Res<PlatformGeneration>,
-    q_player: Single<(&Transform, &mut LinearVelocity, &RayHits, &mut Player)>,
+    mut q_players: Query<(
+        Entity,
+        &Transform,
+        &mut LinearVelocity,
+        &RayHits,
+        &mut Player,
+    )>,
     q_children: Query<&Children>,
-    q_platforms_untouched: Query<(Entity, &Transform), (With<Platform>, Without<Touched>)>,
-    q_platforms_touched: Query<Entity, (With<Platform>, With<Touched>)>,
+    q_platforms: Query<(Entity, &Transform), With<Platform>>,
 ) {
-    let (player_transform, mut velocity, ray_hits, mut player) = q_player.into_inner();
+    for (entity, player_transform, mut velocity, ray_hits, mut player) in &mut q_players {
+        let jump_just_pressed = input.jump_just_pressed(player.id);
+        let jump_just_released = input.jump_just_released(player.id);
+
+        if !player.jump_pressed && jump_just_pressed {
+            player.jump_pressed = true;
+        } else if player.jump_pressed && jump_just_released {
+            player.jump_pressed = false;
+        }
 
-    let jump_just_pressed = keyboard.just_pressed(KeyCode::Space)
-        || mouse.just_pressed(MouseButton::Left)
-        || touches.iter_just_pressed().any(|touch| touch.id() == 0);
+        // Stop at the end of a finite course, or when out of the race
+        if game.finished || player.is_done() {
+            *velocity = LinearVelocity::ZERO;
+            continue;
+        }
 
-    let jump_just_released = keyboard.just_released(KeyCode::Space)
-        || mouse.just_released(MouseButton::Left)
-        || touches.iter_just_released().any(|touch| touch.id() == 0);
+        // Start on first jump
+        if !player.started {
+            if player.jump_pressed {
+                player.started = true;
+                game.started = true;
+                commands.trigger_targets(
+                    RunStarted {
+                        player: player.id,
+                        mode: run_settings.mode,
+                        seed: platform_gen.seed,
+                    },
+                    entity,
+                );
+            } else {
+                continue;
+            }
+        }
 
-    if !player.jump_pressed && jump_just_pressed {
-        player.jump_pressed = true;
-    } else if player.jump_pressed && jump_just_released {
-        player.jump_pressed = false;
-    }
+        let moved = move_player(
+            &time,
+            &game,
+            &run_settings,
+            &course,
+            &platform_gen,
+            player_transform,
+            &mut velocity,
+            ray_hits,
+            &mut player,
+            &q_children,
+            &q_platforms,
+        );
+
+        if let Some(coyote) = moved.jumped {
+            commands.trigger_targets(
+                Jumped {
+                    player: player.id,
+                    coyote,
+                },
+                entity,
+            );
+        }
 
-    // Start on first jump
-    if !game.started {
-        if player.jump_pressed {
-            game.started = true;
-        } else {
-            return;
+        if moved.boosted {
+            commands.trigger_targets(JumpBoosted, entity);
+        }
+
+        if let Some(impact) = moved.landed {
+            commands.trigger_targets(Landed { impact }, entity);
         }
     }
+}
+
+/// What happened during a movement step, for the gameplay events
+struct Moved {
+    /// Whether the jump was in the coyote time
+    jumped: Option<bool>,
+    boosted: bool,
+    /// Vertical speed when hitting the ground
+    landed: Option<f32>,
+}
+
+fn move_player(
+    time: &Time,
+    game: &Game,
+    run_settings: &RunSettings,
+    course: &Course,
+    platform_gen: &PlatformGeneration,
+    player_transform: &Transform,
+    velocity: &mut LinearVelocity,
+    ray_hits: &RayHits,
+    player: &mut Player,
+    q_children: &Query<&Children>,
+    q_platforms: &Query<(Entity, &Transform), With<Platform>>,
+) -> Moved {
+    let tuning = run_settings.tuning;
 
     // Jump & Gravity
     let mut velocity_y = velocity.y;
@@ -117,54 +585,52 @@ pub fn player_movement(
         .iter()
         .filter(|hit| hit.time_of_impact < 1.0)
         .any(|hit| {
-            q_platforms_touched
-                .iter()
-                .chain(q_platforms_untouched.iter().map(|p| p.0))
-                .any(|p| p == hit.entity || q_children.iter_descendants(p).any(|c| c == hit.entity))
+            q_platforms.iter().any(|(p, _)| {
+                p == hit.entity || q_children.iter_descendants(p).any(|c| c == hit.entity)
+            })
         });
 
+    let landed =
+        (is_grounded && player.air_time > LANDING_MIN_AIR_TIME).then_some(-velocity_y.min(0.0));
+
     if is_grounded {
         player.coyote_time.reset();
+        player.air_time = 0.0;
+    } else {
+        player.air_time += time.delta_secs();
     }
 
     let is_grounded_coyote = is_grounded || !player.coyote_time.tick(time.delta()).finished();
 
+    // Not already going up from a jump still in the coyote time
+    let jumped = is_grounded_coyote && player.jump_pressed && velocity_y <= 0.0;
+
     if is_grounded_coyote {
         velocity_y = 0.0;
 
         if player.jump_pressed {
-            velocity_y += JUMP;
+            velocity_y += tuning.jump();
             player.jump_boost_duration.reset();
         }
     } else {
-        velocity_y += GRAVITY * time.delta_secs();
+        velocity_y += tuning.gravity() * time.delta_secs();
     }
 
     player.jump_boost_duration.tick(time.delta());
 
-    if player.jump_pressed
-        && !player.jump_boost_duration.finished()
-        && player.jump_boost_duration.elapsed_secs() > JUMP_BOOST_MIN_TIME
-    {
-        velocity_y += JUMP_BOOST_SPEED * time.delta_secs();
-    }
-
-    // MOVEMENT: move towards the closest platform without Touched
-    let mut next_untouched = None;
-    let mut min_distance = f32::MAX;
-
-    for (_, platform_transform) in &q_platforms_untouched {
-        let distance = platform_transform
-            .translation
-            .distance(player_transform.translation);
+    let boosting = player.is_boosting();
 
-        if distance < min_distance {
-            next_untouched = Some(platform_transform.translation);
-            min_distance = distance;
-        }
+    if boosting {
+        velocity_y += JUMP_BOOST_SPEED * time.delta_secs();
     }
 
-    let next_untouched_position = next_untouched.unwrap_or(platform_gen.next_platform_position);
+    // MOVEMENT: move towards the next platform in the course
+    let next_untouched_position = course
+        .next(player.id)
+        .and_then(|next| q_platforms.get(next.entity).ok())
+        .map_or(platform_gen.next_platform_position, |(_, transform)| {
+            transform.translation
+        });
 
     // get the 2d direction towards the platform, normalized
     let direction_2d = (next_untouched_position.xz() - player_transform.translation.xz())
@@ -173,102 +639,235 @@ pub fn player_movement(
 
     player.last_direction_2d = direction_2d;
 
-    let forward_speed = SPEED * (game.difficulty() + 1.0);
-    let movement_2d = direction_2d * forward_speed;
+    player.forward_speed = tuning.speed() * (game.difficulty() + 1.0);
+    let movement_2d = direction_2d * player.forward_speed;
 
     velocity.0 = Vec3::new(movement_2d.x, velocity_y, movement_2d.y);
+
+    Moved {
+        jumped: jumped.then_some(!is_grounded),
+        boosted: boosting
+            && player.jump_boost_duration.elapsed_secs() - time.delta_secs() <= JUMP_BOOST_MIN_TIME,
+        landed,
+    }
 }
 
 pub fn camera_rotation(
     time: Res<Time>,
+    course: Res<Course>,
     platform_gen: Res<PlatformGeneration>,
-    mut camera: Query<(&mut Transform, &GlobalTransform), With<Camera3d>>,
-    platforms_unhovered: Query<&Transform, (With<Platform>, Without<Hovered>, Without<Camera3d>)>,
+    mut cameras: Query<(&mut Transform, &GlobalTransform, &CameraPivot)>,
+    q_platforms: Query<&Transform, (With<Platform>, Without<CameraPivot>)>,
 ) {
-    let (mut camera_transform, camera_global_transform) = camera.single_mut();
+    for (mut camera_transform, camera_global_transform, camera) in &mut cameras {
+        let position = |platform: Option<&CoursePlatform>| {
+            platform
+                .and_then(|platform| q_platforms.get(platform.entity).ok())
+                .map(|transform| transform.translation)
+        };
 
-    // CAMERA: look at the closest platform without Hovered
-    let mut next_unhovered = None;
-    let mut min_distance = f32::MAX;
+        let next = position(course.next(camera.0)).unwrap_or(platform_gen.next_platform_position);
+        let after_next = position(course.next_after_next(camera.0)).unwrap_or(next);
 
-    for platform_transform in &platforms_unhovered {
-        let distance = platform_transform
-            .translation
-            .distance(camera_global_transform.translation());
+        let eye = camera_global_transform.translation();
+        let target = look_ahead(eye, next, after_next);
 
-        if distance < min_distance {
-            next_unhovered = Some(platform_transform.translation);
-            min_distance = distance;
-        }
+        rotate_camera(&time, &mut camera_transform, eye, target);
     }
+}
 
-    let next_unhovered_position = next_unhovered.unwrap_or(platform_gen.next_platform_position);
+/// Point between the next two platforms, sliding towards the second one while nearing the first.
+/// Going smoothly from one to the other, rather than switching, keeps the camera from jittering
+fn look_ahead(eye: Vec3, next: Vec3, after_next: Vec3) -> Vec3 {
+    let distance = eye.xz().distance(next.xz());
+    let t = 1.0 - (distance / LOOK_AHEAD_DISTANCE).clamp(0.0, 1.0);
+    let t = t * t * (3.0 - 2.0 * t);
+
+    next.lerp(after_next, t * LOOK_AHEAD) + Vec3::Y * 2.0
+}
 
-    // Rotate the camera towards the platform
-    let direction = next_unhovered_position - camera_global_transform.translation() + Vec3::Y * 2.0;
-    // code from Transform::look_at
-    let back = -direction.try_normalize().unwrap_or(Vec3::NEG_Z);
-    let right = Vec3::Y.cross(back).try_normalize().unwrap_or(Vec3::Z);
-    let up = back.cross(right);
-    let rotation = Quat::from_mat3(&Mat3::from_cols(right, up, back));
+fn rotate_camera(time: &Time, camera_transform: &mut Transform, eye: Vec3, target: Vec3) {
+    let Ok(direction) = Dir3::new(target - eye) else {
+        return;
+    };
 
-    // frame-independent lerp
-    camera_transform.rotation = camera_transform
+    let dt = time.delta_secs();
+    let (yaw, pitch, _) = camera_transform.rotation.to_euler(EulerRot::YXZ);
+
+    // Exponential decay towards the target, the same on every frame rate
+    let target = Transform::default().looking_to(direction, Vec3::Y).rotation;
+    let tracked = camera_transform
         .rotation
-        .lerp(rotation, CAMERA_ROTATION_SPEED * time.delta_secs());
+        .slerp(target, 1.0 - (-CAMERA_TRACKING_SPEED * dt).exp());
+    let (tracked_yaw, tracked_pitch, _) = tracked.to_euler(EulerRot::YXZ);
+
+    // Limit the turn rates, the shortest way around for the yaw
+    let max_yaw = CAMERA_MAX_YAW_RATE * dt;
+    let max_pitch = CAMERA_MAX_PITCH_RATE * dt;
+    let yaw_step = ((tracked_yaw - yaw + PI).rem_euclid(2.0 * PI) - PI).clamp(-max_yaw, max_yaw);
+    let pitch_step = (tracked_pitch - pitch).clamp(-max_pitch, max_pitch);
+
+    camera_transform.rotation = Quat::from_euler(
+        EulerRot::YXZ,
+        yaw + yaw_step,
+        (pitch + pitch_step).clamp(-CAMERA_MAX_PITCH, CAMERA_MAX_PITCH),
+        0.0,
+    );
 }
 
 pub fn player_touch_platform(
     mut commands: Commands,
     mut game: ResMut<Game>,
+    mut course: ResMut<Course>,
     collisions: Res<Collisions>,
-    player: Single<Entity, With<Player>>,
-    q_platforms_untouched: Query<Entity, (With<Platform>, Without<Touched>)>,
+    mut q_players: Query<(Entity, &mut Player)>,
+    mut q_platforms_untouched: Query<
+        (Entity, &Platform, &mut TouchedBy, Has<MovingPlatform>),
+        Without<Touched>,
+    >,
     q_children: Query<&Children>,
 ) {
-    let player_entity = *player;
-
-    if let Some(entity) = q_platforms_untouched.iter().find(|platform| {
-        collisions.contains(player_entity, *platform)
-            || q_children
-                .iter_descendants(*platform)
-                .any(|c| collisions.contains(c, player_entity))
-    }) {
-        commands.entity(entity).insert(Touched(Timer::from_seconds(
-            TOUCHED_PLATFORM_TTL,
-            TimerMode::Once,
-        )));
-        game.points += 1;
-        commands.trigger(SpawnPlatform);
+    // Platforms start crumbling once every player still racing touched them
+    let mut racing = PlayerSet::default();
+    for (_, player) in &q_players {
+        if !player.out {
+            racing.insert(player.id);
+        }
+    }
+
+    for (player_entity, mut player) in &mut q_players {
+        let Some((entity, platform, mut touched_by, moving)) = q_platforms_untouched
+            .iter_mut()
+            .find(|(entity, _, touched_by, _)| {
+                !touched_by.0.contains(player.id)
+                    && (collisions.contains(player_entity, *entity)
+                        || q_children
+                            .iter_descendants(*entity)
+                            .any(|c| collisions.contains(c, player_entity)))
+            })
+        else {
+            continue;
+        };
+
+        touched_by.0.insert(player.id);
+        if touched_by.0.contains_all(racing) {
+            commands.entity(entity).insert(Touched(Timer::from_seconds(
+                TOUCHED_PLATFORM_TTL,
+                TimerMode::Once,
+            )));
+        }
+
+        player.points += course
+            .get(platform.index)
+            .map_or(1, |platform| platform.points);
+        game.points = game.points.max(player.points);
+
+        // Landed on again after a rewind, the landing already counted the first time
+        if course.is_replay(player.id, platform.index) {
+            course.reach(player.id, platform.index);
+            continue;
+        }
+
+        commands.trigger_targets(
+            PlatformLanded {
+                player: player.id,
+                index: platform.index,
+                points: player.points,
+                moving,
+                missed: course.skipped(player.id, platform.index),
+            },
+            player_entity,
+        );
+    }
+}
+
+// Pick a branch by steering towards it when the course forks
+pub fn player_choose_branch(
+    input: PlayerInput,
+    mut course: ResMut<Course>,
+    q_players: Query<(&Transform, &Player)>,
+) {
+    for (transform, player) in &q_players {
+        let steer = input.steer(player.id);
+
+        if steer == 0.0 {
+            continue;
+        }
+
+        let right = Vec2::new(-player.last_direction_2d.y, player.last_direction_2d.x);
+
+        let choice = course
+            .branches(player.id)
+            .max_by(|a, b| {
+                let side_a = (a.position.xz() - transform.translation.xz()).dot(right) * steer;
+                let side_b = (b.position.xz() - transform.translation.xz()).dot(right) * steer;
+                side_a.total_cmp(&side_b)
+            })
+            .map(|platform| platform.index);
+
+        if let Some(choice) = choice {
+            if course.choice(player.id) != Some(choice) {
+                course.choose(player.id, choice);
+            }
+        }
+    }
+}
+
+pub fn player_touch_hazard(
+    collisions: Res<Collisions>,
+    mut q_players: Query<(Entity, &mut LinearVelocity, &mut Player)>,
+    q_hazards: Query<Entity, With<Hazard>>,
+) {
+    for (player_entity, mut velocity, mut player) in &mut q_players {
+        if q_hazards
+            .iter()
+            .any(|hazard| collisions.contains(player_entity, hazard))
+        {
+            player.set_death_cause(DeathCause::Hazard);
+
+            // Falling fast enough triggers the reset
+            velocity.y = -100.0;
+        }
     }
 }
 
-pub fn player_hover_platform(
+// Runs after the rewind assist, and what can make the player fall
+pub fn player_fall(
     mut commands: Commands,
-    q_player: Single<&RayHits, With<Player>>,
-    q_platforms_unhovered: Query<Entity, (With<Platform>, Without<Hovered>)>,
-    q_children: Query<&Children>,
+    mut q_players: Query<(Entity, &Transform, &LinearVelocity, &mut Player)>,
 ) {
-    let ray_hits = q_player.into_inner();
-
-    if let Some(platform) = q_platforms_unhovered.iter().find(|platform| {
-        ray_hits.iter().any(|hit| {
-            *platform == hit.entity
-                || q_children
-                    .iter_descendants(*platform)
-                    .any(|c| c == hit.entity)
-        })
-    }) {
-        commands.entity(platform).try_insert(Hovered);
+    for (entity, transform, velocity, mut player) in &mut q_players {
+        if velocity.y >= -20.0 || !player.started || player.out {
+            continue;
+        }
+
+        let cause = player.death_cause.take().unwrap_or(DeathCause::Fall);
+
+        commands.trigger_targets(
+            PlayerDied {
+                player: player.id,
+                cause,
+                position: transform.translation,
+                points: player.points,
+            },
+            entity,
+        );
     }
 }
 
 pub fn force_respawn(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    q_player: Single<(&mut Transform, &mut LinearVelocity), With<Player>>,
+    mut rewind: ResMut<RewindBuffer>,
+    q_player: Single<(&mut Transform, &mut LinearVelocity, &mut Player)>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::KeyR) {
-        let (mut transform, mut velocity) = q_player.into_inner();
+    let (mut transform, mut velocity, mut player) = q_player.into_inner();
+
+    // Nothing to respawn from before the first jump
+    if keyboard_input.just_pressed(KeyCode::KeyR) && player.started {
+        // A forced respawn is never rewound
+        rewind.forget();
+        player.set_death_cause(DeathCause::Restart);
+
         transform.translation.y = -100.0;
         velocity.y = -100.0;
     }
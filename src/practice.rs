@@ -0,0 +1,134 @@
+use avian3d::prelude::*;
+use bevy::{pbr::NotShadowCaster, prelude::*};
+
+use crate::{
+    course::Course,
+    events::DeathCause,
+    game::{DifficultyCurve, Game, RunSettings, NB_PLATFORMS_INIT},
+    platforms::{Platform, PlatformGeneration, SpawnPlatform, Touched},
+    player::Player,
+    rewind::RewindBuffer,
+};
+
+pub const CHECKPOINT_INTERVAL: u32 = 10;
+
+/// Practice checkpoint platform, with the generation state to regenerate the course from it
+#[derive(Component)]
+pub struct Checkpoint {
+    generation: Box<PlatformGeneration>,
+}
+
+#[derive(Resource)]
+pub struct CheckpointAssets {
+    beacon_mesh: Handle<Mesh>,
+    beacon_material: Handle<StandardMaterial>,
+}
+
+/// Last checkpoint reached in practice mode, falling respawns the player on it
+#[derive(Resource)]
+pub struct PracticeCheckpoint {
+    pub generation: PlatformGeneration,
+    pub position: Vec3,
+    pub points: u32,
+}
+
+pub fn load_checkpoint_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(CheckpointAssets {
+        beacon_mesh: meshes.add(Cylinder::new(0.15, 6.0)),
+        beacon_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.3, 1.0, 0.5, 0.5),
+            emissive: LinearRgba::rgb(0.5, 3.0, 1.0),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+pub fn spawn_checkpoint(
+    commands: &mut Commands,
+    checkpoint_assets: &CheckpointAssets,
+    platform: Entity,
+    generation: PlatformGeneration,
+) {
+    commands
+        .entity(platform)
+        .insert(Checkpoint {
+            generation: Box::new(generation),
+        })
+        .with_children(|c| {
+            c.spawn((
+                Mesh3d(checkpoint_assets.beacon_mesh.clone()),
+                MeshMaterial3d(checkpoint_assets.beacon_material.clone()),
+                Transform::from_translation(Vec3::Y * 3.0),
+                NotShadowCaster,
+            ));
+        });
+}
+
+pub fn save_checkpoint(
+    mut commands: Commands,
+    game: Res<Game>,
+    course: Res<Course>,
+    query: Query<(&Platform, &Checkpoint, &Transform), Added<Touched>>,
+) {
+    let Some(current) = course.current(0) else {
+        return;
+    };
+
+    // Skipped checkpoints are touched too when retired, only keep the one reached
+    if let Some((_, checkpoint, transform)) = query
+        .iter()
+        .find(|(platform, _, _)| platform.index == current.index)
+    {
+        commands.insert_resource(PracticeCheckpoint {
+            generation: (*checkpoint.generation).clone(),
+            position: transform.translation,
+            points: game.points.saturating_sub(current.points),
+        });
+    }
+}
+
+/// Regenerate the same course from the checkpoint, keeping the score at that point
+pub fn restart_from_checkpoint(
+    mut commands: Commands,
+    checkpoint: &PracticeCheckpoint,
+    curve: DifficultyCurve,
+) {
+    commands.insert_resource(Game {
+        points: checkpoint.points,
+        curve,
+        ..default()
+    });
+    commands.insert_resource(checkpoint.generation.clone());
+    commands.insert_resource(Course::default());
+
+    for _ in 0..NB_PLATFORMS_INIT {
+        commands.trigger(SpawnPlatform);
+    }
+}
+
+pub fn toggle_practice(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut run_settings: ResMut<RunSettings>,
+    mut rewind: ResMut<RewindBuffer>,
+    q_player: Single<(&mut LinearVelocity, &mut Player)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        run_settings.practice = !run_settings.practice;
+        commands.remove_resource::<PracticeCheckpoint>();
+        rewind.forget();
+
+        // Falling fast enough triggers the reset, once the run started
+        let (mut velocity, mut player) = q_player.into_inner();
+        if player.started {
+            player.set_death_cause(DeathCause::Restart);
+            velocity.y = -100.0;
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{DifficultyCurve, RunSettings},
+    player::TuningPreset,
+    storage,
+};
+
+const SAVE_KEY: &str = "profiles";
+
+pub const PROFILE_NAME_MAX_LEN: usize = 12;
+
+/// Settings restored when a profile is picked
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProfileSettings {
+    pub curve: DifficultyCurve,
+    pub tuning: TuningPreset,
+    pub practice: bool,
+    pub rewind: bool,
+}
+
+impl ProfileSettings {
+    fn from_run_settings(run_settings: &RunSettings) -> Self {
+        Self {
+            curve: run_settings.curve,
+            tuning: run_settings.tuning,
+            practice: run_settings.practice,
+            rewind: run_settings.rewind,
+        }
+    }
+
+    fn apply(self, run_settings: &mut RunSettings) {
+        run_settings.curve = self.curve;
+        run_settings.tuning = self.tuning;
+        run_settings.practice = self.practice;
+        run_settings.rewind = self.rewind;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub id: u32,
+    pub name: String,
+    pub settings: ProfileSettings,
+}
+
+impl Profile {
+    /// Save key of this profile's high scores.
+    /// The first profile keeps the plain key, the saves from before profiles are its own
+    pub fn save_key(&self, key: &str) -> String {
+        match self.id {
+            0 => key.to_string(),
+            id => format!("{key}_{id}"),
+        }
+    }
+}
+
+/// Named local profiles sharing the machine, the current one plays
+#[derive(Resource, Serialize, Deserialize)]
+pub struct Profiles {
+    profiles: Vec<Profile>,
+    current: usize,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        storage::load::<Self>(SAVE_KEY)
+            .filter(|profiles| profiles.current < profiles.profiles.len())
+            .unwrap_or_else(|| Self {
+                profiles: vec![Profile {
+                    id: 0,
+                    name: "PLAYER".to_string(),
+                    settings: ProfileSettings::default(),
+                }],
+                current: 0,
+            })
+    }
+}
+
+impl Profiles {
+    pub fn current(&self) -> &Profile {
+        &self.profiles[self.current]
+    }
+
+    /// Switch to the next profile and restore its settings
+    pub fn cycle(&mut self, run_settings: &mut RunSettings) {
+        self.current = (self.current + 1) % self.profiles.len();
+        self.current().settings.apply(run_settings);
+        storage::save(SAVE_KEY, self);
+    }
+
+    /// Add a profile with the current settings and switch to it, names are unique
+    pub fn create(&mut self, name: &str, run_settings: &RunSettings) {
+        let name = match name.trim() {
+            "" => format!("PLAYER {}", self.profiles.len() + 1),
+            name => name.chars().take(PROFILE_NAME_MAX_LEN).collect(),
+        };
+
+        if let Some(index) = self.profiles.iter().position(|p| p.name == name) {
+            self.current = index;
+        } else {
+            self.profiles.push(Profile {
+                id: self
+                    .profiles
+                    .iter()
+                    .map(|p| p.id + 1)
+                    .max()
+                    .unwrap_or_default(),
+                name,
+                settings: ProfileSettings::from_run_settings(run_settings),
+            });
+            self.current = self.profiles.len() - 1;
+        }
+
+        storage::save(SAVE_KEY, self);
+    }
+}
+
+pub fn apply_profile_settings(profiles: Res<Profiles>, mut run_settings: ResMut<RunSettings>) {
+    profiles.current().settings.apply(&mut run_settings);
+}
+
+/// Keep the current profile's settings in sync with the menu and the in-game toggles
+pub fn save_profile_settings(mut profiles: ResMut<Profiles>, run_settings: Res<RunSettings>) {
+    if !run_settings.is_changed() {
+        return;
+    }
+
+    let settings = ProfileSettings::from_run_settings(&run_settings);
+    let current = profiles.current;
+
+    if profiles.profiles[current].settings != settings {
+        profiles.profiles[current].settings = settings;
+        storage::save(SAVE_KEY, &*profiles);
+    }
+}
@@ -0,0 +1,48 @@
+// Online race protocol, shared by the game and the relay server (src/bin/relay.rs).
+// Messages are RON text frames over WebSocket. Racers in the same room (a run code) get each
+// other's states, the relay only forwards them: ghosts don't collide, there is no physics to sync.
+
+use serde::{Deserialize, Serialize};
+
+/// Bump when the messages change, the relay rejects clients of another version
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const DEFAULT_RELAY_PORT: u16 = 9001;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RacerState {
+    pub position: (f32, f32, f32),
+    pub points: u32,
+    /// Distance along the course
+    pub distance: f32,
+    pub finish_time: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ClientMessage {
+    /// First message of a client, to race with the others in the room
+    Join {
+        version: u32,
+        room: String,
+        name: String,
+    },
+    /// Sent a few times per second while racing
+    State(RacerState),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ServerMessage {
+    Joined { id: u32 },
+    Rejected { reason: String },
+    RacerJoined { id: u32, name: String },
+    RacerLeft { id: u32 },
+    RacerState { id: u32, state: RacerState },
+}
+
+pub fn encode<T: Serialize>(message: &T) -> Option<String> {
+    ron::to_string(message).ok()
+}
+
+pub fn decode<T: for<'de> Deserialize<'de>>(text: &str) -> Option<T> {
+    ron::from_str(text).ok()
+}
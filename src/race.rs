@@ -0,0 +1,211 @@
+use avian3d::prelude::*;
+use bevy::{prelude::*, render::camera::Viewport, window::PrimaryWindow};
+
+use crate::{
+    course::Course,
+    events::{PlayerDied, RunEndReason, RunEnded},
+    game::{hud, Game, RunSettings},
+    player::{spawn_local_player, Player, PlayerCamera, PlayerInput},
+    skybox::{SkyboxCustom, SkyboxCustomMaterial},
+    AppState,
+};
+
+/// Local race standings, best first
+#[derive(Resource, Default)]
+pub struct RaceResults {
+    pub standings: Vec<Standing>,
+}
+
+pub struct Standing {
+    pub player: usize,
+    pub points: u32,
+    pub finish_time: Option<f32>,
+}
+
+#[derive(Component)]
+pub struct ResultsScreen;
+
+/// Spawn the other local players with their camera and HUD, the first one is always there
+pub fn spawn_racers(
+    mut commands: Commands,
+    run_settings: Res<RunSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_skybox: Query<&MeshMaterial3d<SkyboxCustomMaterial>, With<SkyboxCustom>>,
+) {
+    if run_settings.players == 1 {
+        return;
+    }
+
+    let skybox_material = q_skybox.iter().next().cloned();
+
+    for id in 1..run_settings.players {
+        let camera = spawn_local_player(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            id,
+            skybox_material.clone(),
+        );
+
+        commands.spawn((hud(id, ""), TargetCamera(camera)));
+    }
+}
+
+/// Split the window between the players: top and bottom for two, quarters for more
+pub fn update_viewports(
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut q_cameras: Query<(&mut Camera, &PlayerCamera)>,
+) {
+    let count = q_cameras.iter().count() as u32;
+    let size = window.physical_size();
+
+    for (mut camera, player_camera) in &mut q_cameras {
+        let id = player_camera.0 as u32;
+
+        let viewport = match count {
+            0 | 1 => None,
+            2 => Some(Viewport {
+                physical_position: UVec2::new(0, id * size.y / 2),
+                physical_size: UVec2::new(size.x, size.y / 2),
+                ..default()
+            }),
+            _ => Some(Viewport {
+                physical_position: UVec2::new(id % 2 * size.x / 2, id / 2 * size.y / 2),
+                physical_size: size / 2,
+                ..default()
+            }),
+        };
+
+        let changed = match (&camera.viewport, &viewport) {
+            (Some(a), Some(b)) => {
+                a.physical_position != b.physical_position || a.physical_size != b.physical_size
+            }
+            (a, b) => a.is_some() != b.is_some(),
+        };
+
+        if changed && size.min_element() > 0 {
+            camera.viewport = viewport;
+        }
+    }
+}
+
+/// In a race, dying is out instead of restarting
+pub fn race_fall(
+    trigger: Trigger<PlayerDied>,
+    run_settings: Res<RunSettings>,
+    mut course: ResMut<Course>,
+    mut q_players: Query<(&mut LinearVelocity, &mut Player)>,
+) {
+    if run_settings.players == 1 {
+        return;
+    }
+
+    if let Ok((mut velocity, mut player)) = q_players.get_mut(trigger.entity()) {
+        player.out = true;
+        *velocity = LinearVelocity::ZERO;
+        course.retire(player.id);
+    }
+}
+
+/// The race ends once every player is out or at the goal
+pub fn end_race(
+    mut commands: Commands,
+    mut game: ResMut<Game>,
+    mut next_state: ResMut<NextState<AppState>>,
+    q_players: Query<(Entity, &Player)>,
+) {
+    if q_players.iter().any(|(_, player)| !player.is_done()) {
+        return;
+    }
+
+    game.finished = true;
+
+    for (entity, player) in &q_players {
+        commands.trigger_targets(
+            RunEnded {
+                player: player.id,
+                reason: RunEndReason::Finished,
+                points: player.points,
+                finish_time: player.finish_time,
+            },
+            entity,
+        );
+    }
+
+    let mut standings: Vec<Standing> = q_players
+        .iter()
+        .map(|(_, player)| Standing {
+            player: player.id,
+            points: player.points,
+            finish_time: player.finish_time,
+        })
+        .collect();
+
+    // Finishers first by time, then by score
+    standings.sort_by(|a, b| match (a.finish_time, b.finish_time) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => b.points.cmp(&a.points),
+    });
+
+    commands.insert_resource(RaceResults { standings });
+    next_state.set(AppState::Results);
+}
+
+pub fn spawn_results(mut commands: Commands, results: Res<RaceResults>) {
+    let mut text = "RESULTS\n".to_string();
+
+    for (rank, standing) in results.standings.iter().enumerate() {
+        let result = match standing.finish_time {
+            Some(time) => format!("{time:.2}s"),
+            None => format!("{} POINTS", standing.points),
+        };
+
+        text = format!(
+            "{text}\n{}. PLAYER {}  {result}",
+            rank + 1,
+            standing.player + 1
+        );
+    }
+
+    text = format!("{text}\n\nJUMP TO CONTINUE");
+
+    commands
+        .spawn((
+            ResultsScreen,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        ))
+        .with_child((
+            Text::new(text),
+            TextFont {
+                font_size: 40.0,
+                ..default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+        ));
+}
+
+pub fn despawn_results(mut commands: Commands, query: Query<Entity, With<ResultsScreen>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn leave_results(
+    input: PlayerInput,
+    run_settings: Res<RunSettings>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if (0..run_settings.players).any(|player| input.jump_just_pressed(player)) {
+        next_state.set(AppState::Menu);
+    }
+}
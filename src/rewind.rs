@@ -0,0 +1,190 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use crate::{
+    course::Course,
+    game::{Game, RunSettings},
+    platforms::{MovingPlatform, Platform, Touched, TouchedBy},
+    player::{Player, PlayerSet},
+    vfx::Crumbling,
+};
+
+const REWIND_SECONDS: f32 = 3.0;
+const REWIND_SAMPLE_INTERVAL: f32 = 0.1;
+const REWIND_PLATFORMS_RADIUS: f32 = 40.0;
+pub const REWINDS_PER_RUN: u32 = 3;
+
+struct PlatformState {
+    entity: Entity,
+    moving: Option<(f32, bool)>,
+    touched: Option<Timer>,
+    touched_by: PlayerSet,
+}
+
+struct RewindFrame {
+    transform: Transform,
+    velocity: LinearVelocity,
+    player: Player,
+    points: u32,
+    course: Course,
+    platforms: Vec<PlatformState>,
+}
+
+/// Recent states of the player and the platforms around, to go back in time instead of falling
+#[derive(Resource)]
+pub struct RewindBuffer {
+    frames: VecDeque<RewindFrame>,
+    sample: Timer,
+    pub uses_left: u32,
+}
+
+impl Default for RewindBuffer {
+    fn default() -> Self {
+        Self {
+            frames: VecDeque::new(),
+            sample: Timer::from_seconds(REWIND_SAMPLE_INTERVAL, TimerMode::Repeating),
+            uses_left: REWINDS_PER_RUN,
+        }
+    }
+}
+
+impl RewindBuffer {
+    /// Drop the recorded states, they don't match the course anymore
+    pub fn forget(&mut self) {
+        self.frames.clear();
+    }
+}
+
+pub fn record_rewind(
+    time: Res<Time>,
+    game: Res<Game>,
+    course: Res<Course>,
+    run_settings: Res<RunSettings>,
+    mut rewind: ResMut<RewindBuffer>,
+    q_player: Single<(&Transform, &LinearVelocity, &Player)>,
+    q_platforms: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&MovingPlatform>,
+            Option<&Touched>,
+            &TouchedBy,
+        ),
+        With<Platform>,
+    >,
+) {
+    if !run_settings.rewinding()
+        || !game.started
+        || !rewind.sample.tick(time.delta()).just_finished()
+    {
+        return;
+    }
+
+    let (transform, velocity, player) = q_player.into_inner();
+
+    // Already falling
+    if velocity.y < -20.0 {
+        return;
+    }
+
+    let platforms = q_platforms
+        .iter()
+        .filter(|(_, platform_transform, _, _, _)| {
+            platform_transform
+                .translation
+                .distance(transform.translation)
+                < REWIND_PLATFORMS_RADIUS
+        })
+        .map(|(entity, _, moving, touched, touched_by)| PlatformState {
+            entity,
+            moving: moving.map(|moving| (moving.progress, moving.going_negative)),
+            touched: touched.map(|touched| touched.0.clone()),
+            touched_by: touched_by.0,
+        })
+        .collect();
+
+    rewind.frames.push_back(RewindFrame {
+        transform: *transform,
+        velocity: *velocity,
+        player: player.clone(),
+        points: game.points,
+        course: course.clone(),
+        platforms,
+    });
+
+    while rewind.frames.len() as f32 * REWIND_SAMPLE_INTERVAL > REWIND_SECONDS {
+        rewind.frames.pop_front();
+    }
+}
+
+// Runs before the reset: a fall goes back a few seconds instead, while there are rewinds left
+pub fn rewind_on_fall(
+    mut commands: Commands,
+    run_settings: Res<RunSettings>,
+    mut game: ResMut<Game>,
+    mut course: ResMut<Course>,
+    mut rewind: ResMut<RewindBuffer>,
+    q_player: Single<(&mut Transform, &mut LinearVelocity, &mut Player)>,
+    mut q_platforms: Query<
+        (Option<&mut MovingPlatform>, &mut TouchedBy),
+        (With<Platform>, Without<Crumbling>),
+    >,
+) {
+    let (mut transform, mut velocity, mut player) = q_player.into_inner();
+
+    if velocity.y >= -20.0 || !run_settings.rewinding() || rewind.uses_left == 0 {
+        return;
+    }
+
+    // Crumbling platforms can't come back, go back to the oldest state still standing on solid ground
+    let alive = |entity| q_platforms.contains(entity);
+    let Some(position) = rewind.frames.iter().position(|frame| {
+        frame
+            .course
+            .current(frame.player.id)
+            .is_none_or(|platform| alive(platform.entity))
+    }) else {
+        return;
+    };
+    let Some(frame) = rewind.frames.remove(position) else {
+        return;
+    };
+
+    rewind.forget();
+    rewind.uses_left -= 1;
+
+    *transform = frame.transform;
+    *velocity = frame.velocity;
+    *player = frame.player;
+    game.points = frame.points;
+    course.rewind(frame.course, alive);
+
+    for state in frame.platforms {
+        let Ok((moving, mut touched_by)) = q_platforms.get_mut(state.entity) else {
+            continue;
+        };
+
+        touched_by.0 = state.touched_by;
+
+        if let (Some(mut moving), Some((progress, going_negative))) = (moving, state.moving) {
+            moving.progress = progress;
+            moving.going_negative = going_negative;
+        }
+
+        if let Some(timer) = state.touched {
+            commands.entity(state.entity).insert(Touched(timer));
+        } else {
+            commands.entity(state.entity).remove::<Touched>();
+        }
+    }
+}
+
+pub fn toggle_rewind(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut run_settings: ResMut<RunSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyB) {
+        run_settings.rewind = !run_settings.rewind;
+    }
+}
@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use std::{fmt, str::FromStr};
+
+use crate::{
+    game::{DifficultyCurve, GameMode, RunSettings},
+    platforms::PlatformGeneration,
+    player::TuningPreset,
+};
+
+/// Bump when the course generation or the code layout changes, old codes are then rejected
+pub const RUN_CODE_VERSION: u8 = 2;
+
+// Crockford base32, without the ambiguous I, L, O and U
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const CODE_BYTES: usize = 11;
+const GROUP_LEN: usize = 6;
+
+/// Settings needed to play the same course again, shareable as a short code
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RunCode {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub curve: DifficultyCurve,
+    pub tuning: TuningPreset,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RunCodeError {
+    Invalid,
+    Version(u8),
+}
+
+impl fmt::Display for RunCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid => write!(f, "INVALID CODE"),
+            Self::Version(_) => write!(f, "CODE FROM ANOTHER VERSION"),
+        }
+    }
+}
+
+impl RunCode {
+    pub fn new(run_settings: &RunSettings, seed: u64) -> Self {
+        Self {
+            seed,
+            mode: run_settings.mode,
+            curve: run_settings.curve,
+            tuning: run_settings.tuning,
+        }
+    }
+
+    pub fn apply(self, run_settings: &mut RunSettings) {
+        run_settings.seed = Some(self.seed);
+        run_settings.mode = self.mode;
+        run_settings.curve = self.curve;
+        run_settings.tuning = self.tuning;
+    }
+}
+
+// Layout: version, mode/curve/tuning packed in a byte, seed, checksum
+impl fmt::Display for RunCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = Vec::with_capacity(CODE_BYTES);
+        bytes.push(RUN_CODE_VERSION);
+        bytes.push(
+            (index_of(&GameMode::ALL, self.mode) << 4)
+                | (index_of(&DifficultyCurve::ALL, self.curve) << 2)
+                | index_of(&TuningPreset::ALL, self.tuning),
+        );
+        bytes.extend(self.seed.to_be_bytes());
+        bytes.push(checksum(&bytes));
+
+        let chars = encode_base32(&bytes);
+        let groups: Vec<&str> = chars
+            .as_bytes()
+            .chunks(GROUP_LEN)
+            .map(|group| std::str::from_utf8(group).unwrap_or_default())
+            .collect();
+
+        write!(f, "{}", groups.join("-"))
+    }
+}
+
+impl FromStr for RunCode {
+    type Err = RunCodeError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let bytes = decode_base32(code).ok_or(RunCodeError::Invalid)?;
+
+        // The version is checked first since the rest of the layout depends on it
+        match bytes.first() {
+            Some(&RUN_CODE_VERSION) => {}
+            Some(&version) => return Err(RunCodeError::Version(version)),
+            None => return Err(RunCodeError::Invalid),
+        }
+
+        let Ok([_, packed, s0, s1, s2, s3, s4, s5, s6, s7, sum]) =
+            <[u8; CODE_BYTES]>::try_from(bytes.as_slice())
+        else {
+            return Err(RunCodeError::Invalid);
+        };
+        if checksum(&bytes[..CODE_BYTES - 1]) != sum {
+            return Err(RunCodeError::Invalid);
+        }
+
+        Ok(Self {
+            seed: u64::from_be_bytes([s0, s1, s2, s3, s4, s5, s6, s7]),
+            mode: from_index(&GameMode::ALL, packed >> 4)?,
+            curve: from_index(&DifficultyCurve::ALL, (packed >> 2) & 0b11)?,
+            tuning: from_index(&TuningPreset::ALL, packed & 0b11)?,
+        })
+    }
+}
+
+fn index_of<T: PartialEq>(all: &[T], value: T) -> u8 {
+    all.iter().position(|v| *v == value).unwrap_or_default() as u8
+}
+
+fn from_index<T: Copy>(all: &[T], index: u8) -> Result<T, RunCodeError> {
+    all.get(index as usize)
+        .copied()
+        .ok_or(RunCodeError::Invalid)
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0xA5, |sum: u8, byte| sum.rotate_left(3) ^ byte)
+}
+
+fn encode_base32(bytes: &[u8]) -> String {
+    let mut code = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            code.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+
+    if bits > 0 {
+        code.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+
+    code
+}
+
+/// Lenient decoding: case, dashes and spaces are ignored, lookalike letters are read as digits
+fn decode_base32(code: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in code.chars().filter(|c| !matches!(c, '-' | ' ')) {
+        let c = match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        };
+        let value = ALPHABET.iter().position(|a| *a as char == c)?;
+
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(bytes)
+}
+
+/// Code being typed in the menu, and the code of the last run played
+#[derive(Resource, Default)]
+pub struct RunCodeInput {
+    pub text: String,
+    pub error: Option<RunCodeError>,
+    pub last: Option<RunCode>,
+    /// Play the typed code as soon as the menu opens
+    pub autostart: bool,
+}
+
+pub fn remember_run_code(
+    run_settings: Res<RunSettings>,
+    platform_gen: Res<PlatformGeneration>,
+    mut input: ResMut<RunCodeInput>,
+) {
+    input.last = Some(RunCode::new(&run_settings, platform_gen.seed));
+}
+
+/// Open the course given in the page URL like `?run=XXXX`, or on the command line with `--run XXXX`
+pub fn read_startup_run_code(mut input: ResMut<RunCodeInput>) {
+    if let Some(code) = startup_run_code() {
+        input.text = code;
+        input.autostart = true;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn startup_run_code() -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--run");
+    args.next()?;
+    args.next()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn startup_run_code() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|param| param.strip_prefix("run="))
+        .map(str::to_string)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn copy_to_clipboard(text: &str) {
+    if let Err(e) = arboard::Clipboard::new().and_then(|mut c| c.set_text(text)) {
+        warn!("can't copy to clipboard: {e}");
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn paste_from_clipboard() -> Option<String> {
+    arboard::Clipboard::new()
+        .and_then(|mut c| c.get_text())
+        .inspect_err(|e| warn!("can't paste from clipboard: {e}"))
+        .ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn copy_to_clipboard(text: &str) {
+    if let Some(window) = web_sys::window() {
+        // Fire and forget, the browser may refuse without a user gesture
+        let _ = window.navigator().clipboard().write_text(text);
+    }
+}
+
+// Reading the clipboard is async and permission gated on the web, use the URL or type the code
+#[cfg(target_arch = "wasm32")]
+pub fn paste_from_clipboard() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for mode in GameMode::ALL {
+            for curve in DifficultyCurve::ALL {
+                for tuning in TuningPreset::ALL {
+                    for seed in [0, 42, 20_240_229, u64::MAX] {
+                        let code = RunCode {
+                            seed,
+                            mode,
+                            curve,
+                            tuning,
+                        };
+
+                        assert_eq!(code.to_string().parse(), Ok(code));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_parsing() {
+        let code = RunCode {
+            seed: 1234,
+            mode: GameMode::TimeAttack,
+            curve: DifficultyCurve::Steep,
+            tuning: TuningPreset::Snappy,
+        };
+        let text = code.to_string().to_lowercase().replace('-', " ");
+
+        assert_eq!(text.parse(), Ok(code));
+    }
+
+    #[test]
+    fn unknown_version() {
+        let mut bytes = vec![RUN_CODE_VERSION + 1, 0];
+        bytes.extend(42u64.to_be_bytes());
+        bytes.push(checksum(&bytes));
+
+        assert_eq!(
+            encode_base32(&bytes).parse::<RunCode>(),
+            Err(RunCodeError::Version(RUN_CODE_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut bytes = vec![RUN_CODE_VERSION, 0];
+        bytes.extend(42u64.to_be_bytes());
+        bytes.push(checksum(&bytes) ^ 1);
+
+        assert_eq!(
+            encode_base32(&bytes).parse::<RunCode>(),
+            Err(RunCodeError::Invalid)
+        );
+        assert_eq!("".parse::<RunCode>(), Err(RunCodeError::Invalid));
+        assert_eq!("U".parse::<RunCode>(), Err(RunCodeError::Invalid));
+    }
+}
@@ -0,0 +1,217 @@
+use avian3d::prelude::*;
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext, LoadState},
+    prelude::*,
+};
+use rand::prelude::*;
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+pub const SEGMENT_CHANCE: f64 = 0.08;
+
+pub const SEGMENTS: &[&str] = &[
+    "staircase.segment.ron",
+    "slalom.segment.ron",
+    "gauntlet.segment.ron",
+];
+
+/// Hand-designed sequence of platforms, positions are relative to the segment entry point
+#[derive(Asset, TypePath, Deserialize)]
+pub struct SegmentPrefab {
+    /// Base weight when picking a segment
+    pub weight: f32,
+    /// Weight added per point of difficulty
+    #[serde(default)]
+    pub weight_per_difficulty: f32,
+    #[serde(default)]
+    pub min_difficulty: f32,
+    pub platforms: Vec<SegmentPlatform>,
+    /// Where the procedural generation resumes after the segment
+    pub exit: (f32, f32, f32),
+}
+
+#[derive(Deserialize)]
+pub struct SegmentPlatform {
+    pub position: (f32, f32, f32),
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default)]
+    pub moving: bool,
+    #[serde(default)]
+    pub hazards: Vec<SegmentHazard>,
+}
+
+#[derive(Deserialize)]
+pub struct SegmentHazard {
+    pub position: (f32, f32, f32),
+    pub size: (f32, f32, f32),
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+impl SegmentPrefab {
+    pub fn weight(&self, difficulty: f32) -> f32 {
+        if difficulty < self.min_difficulty {
+            0.0
+        } else {
+            (self.weight + self.weight_per_difficulty * difficulty).max(0.0)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SegmentPrefabLoader;
+
+impl AssetLoader for SegmentPrefabLoader {
+    type Asset = SegmentPrefab;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["segment.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct SegmentAssets {
+    prefabs: Vec<Handle<SegmentPrefab>>,
+    hazard_mesh: Handle<Mesh>,
+    hazard_material: Handle<StandardMaterial>,
+}
+
+/// A segment platform placed in the world, waiting to be spawned
+#[derive(Clone)]
+pub struct QueuedPlatform {
+    pub position: Vec3,
+    pub scale: f32,
+    pub moving: bool,
+    pub hazards: Vec<Transform>,
+}
+
+/// Segment being spawned, one platform per `SpawnPlatform`
+#[derive(Clone)]
+pub struct SegmentInProgress {
+    pub platforms: VecDeque<QueuedPlatform>,
+    pub exit: Vec3,
+}
+
+impl SegmentAssets {
+    /// Every prefab loaded or failed to
+    pub fn is_done_loading(&self, assets_server: &AssetServer) -> bool {
+        self.prefabs.iter().all(|handle| {
+            matches!(
+                assets_server.load_state(handle),
+                LoadState::Loaded | LoadState::Failed(_)
+            )
+        })
+    }
+
+    /// Pick a segment weighted by difficulty, placed at `entry`.
+    /// Always draws once from `rng`, so the rest of the course doesn't depend on what is loaded
+    pub fn pick(
+        &self,
+        rng: &mut impl Rng,
+        prefabs: &Assets<SegmentPrefab>,
+        difficulty: f32,
+        entry: Vec3,
+    ) -> Option<SegmentInProgress> {
+        let roll = rng.gen_range(0.0..1.0);
+
+        let loaded = self
+            .prefabs
+            .iter()
+            .filter_map(|handle| prefabs.get(handle))
+            .filter(|prefab| !prefab.platforms.is_empty() && prefab.weight(difficulty) > 0.0)
+            .collect::<Vec<_>>();
+
+        let total: f32 = loaded.iter().map(|prefab| prefab.weight(difficulty)).sum();
+        let mut target = roll * total;
+        let prefab = *loaded
+            .iter()
+            .find(|prefab| {
+                target -= prefab.weight(difficulty);
+                target < 0.0
+            })
+            .or_else(|| loaded.last())?;
+
+        Some(SegmentInProgress {
+            platforms: prefab
+                .platforms
+                .iter()
+                .map(|platform| QueuedPlatform {
+                    position: entry + Vec3::from(platform.position),
+                    scale: platform.scale,
+                    moving: platform.moving,
+                    hazards: platform
+                        .hazards
+                        .iter()
+                        .map(|hazard| {
+                            Transform::from_translation(entry + Vec3::from(hazard.position))
+                                .with_scale(Vec3::from(hazard.size))
+                        })
+                        .collect(),
+                })
+                .collect(),
+            exit: entry + Vec3::from(prefab.exit),
+        })
+    }
+}
+
+/// Obstacle from a segment prefab, touching it is a fall
+#[derive(Component)]
+pub struct Hazard {
+    /// Index of the platform the hazard was spawned with
+    pub platform_index: u32,
+}
+
+pub fn load_segments(
+    mut commands: Commands,
+    assets_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(SegmentAssets {
+        prefabs: SEGMENTS
+            .iter()
+            .map(|path| assets_server.load(format!("segments/{path}")))
+            .collect(),
+        hazard_mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+        hazard_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.8, 0.1, 0.1),
+            emissive: LinearRgba::rgb(2.0, 0.2, 0.1),
+            ..default()
+        }),
+    });
+}
+
+pub fn spawn_hazards(
+    commands: &mut Commands,
+    segment_assets: &SegmentAssets,
+    platform_index: u32,
+    hazards: &[Transform],
+) {
+    for transform in hazards {
+        commands.spawn((
+            Hazard { platform_index },
+            Mesh3d(segment_assets.hazard_mesh.clone()),
+            MeshMaterial3d(segment_assets.hazard_material.clone()),
+            *transform,
+            Collider::cuboid(1.0, 1.0, 1.0),
+            Sensor,
+            RigidBody::Static,
+        ));
+    }
+}
@@ -1,20 +1,133 @@
 use bevy::{
+    pbr::light_consts::lux,
     prelude::*,
     render::{
         mesh::Indices,
         render_asset::RenderAssetUsages,
-        render_resource::{AsBindGroup, PrimitiveTopology, ShaderRef},
+        render_resource::{
+            AsBindGroup, Extent3d, PrimitiveTopology, ShaderRef, TextureDimension, TextureFormat,
+            TextureViewDescriptor, TextureViewDimension,
+        },
     },
 };
+use std::f32::consts::TAU;
+
+/// Time for a new skybox to dissolve over the previous one, `DURATION` in skybox.wgsl
+pub const SKYBOX_TRANSITION_DURATION: f32 = 20.0;
+
+/// Tilt of the sun's path away from the zenith
+const SUN_ORBIT_TILT: f32 = 0.5;
+const MOON_ILLUMINANCE: f32 = 400.0;
 
 #[derive(Component)]
 pub struct SkyboxCustom;
 
+/// Light of the procedural sky, from the sun by day and the moon by night
+#[derive(Component)]
+pub struct Sun;
+
+/// Analytic atmosphere drawn by skybox.wgsl, with stars at night
+#[derive(Clone, Copy, Debug)]
+pub struct ProceduralSky {
+    /// Time of day when the theme starts, in hours from 0 to 24
+    pub start_hour: f32,
+    /// Real seconds for a whole day, the sun stays still without it
+    pub day_length: Option<f32>,
+}
+
+impl ProceduralSky {
+    /// Sky of the themes whose cubemap can't be loaded
+    pub const FALLBACK: Self = Self {
+        start_hour: 16.0,
+        day_length: None,
+    };
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ThemeSky {
+    /// KTX2 cubemap in `assets/skyboxes`, the fallback sky when it fails to load
+    Cubemap(&'static str),
+    Procedural(ProceduralSky),
+}
+
+/// Time of day of the current sky, none for a cubemap
+#[derive(Resource, Default)]
+pub struct SkyClock {
+    sky: Option<ProceduralSky>,
+    hour: f32,
+}
+
+impl SkyClock {
+    pub fn start(&mut self, sky: Option<ProceduralSky>) {
+        *self = Self {
+            sky,
+            hour: sky.map_or(0.0, |sky| sky.start_hour),
+        };
+    }
+
+    /// Direction towards the sun: rising in the east (+X) at 6, highest at 12, below the horizon at night
+    pub fn sun(&self) -> Vec3 {
+        let angle = (self.hour - 6.0) / 24.0 * TAU;
+        Quat::from_rotation_x(SUN_ORBIT_TILT) * Vec3::new(angle.cos(), angle.sin(), 0.0)
+    }
+}
+
+#[derive(Resource)]
+pub struct SkyboxAssets {
+    /// Bound in place of the cubemap of a procedural sky
+    empty_cubemap: Handle<Image>,
+}
+
+pub fn load_skybox_assets(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let mut empty_cubemap = Image::new_fill(
+        Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 6,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    empty_cubemap.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
+    });
+
+    commands.insert_resource(SkyboxAssets {
+        empty_cubemap: images.add(empty_cubemap),
+    });
+}
+
+pub fn spawn_sun(mut commands: Commands) {
+    commands.spawn((
+        Sun,
+        DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+/// One of the two skies of a transition: a cubemap, or the procedural sky lit from `sun`
+pub struct SkyLayer {
+    pub cubemap: Option<Handle<Image>>,
+    pub sun: Vec3,
+}
+
 #[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
 pub struct SkyboxCustomMaterial {
     #[uniform(0)]
     time_t0: f32,
 
+    /// Sun direction of a procedural sky, w is 1 for it and 0 for a cubemap
+    #[uniform(0)]
+    sky1: Vec4,
+    #[uniform(0)]
+    sky2: Vec4,
+
     #[texture(1, dimension = "cube")]
     #[sampler(2)]
     sky_texture1: Handle<Image>,
@@ -22,27 +135,25 @@ pub struct SkyboxCustomMaterial {
     #[texture(3, dimension = "cube")]
     #[sampler(4)]
     sky_texture2: Handle<Image>,
-
-    #[cfg(target_arch = "wasm32")]
-    _padding_0: f32,
-    #[cfg(target_arch = "wasm32")]
-    _padding_1: f32,
-    #[cfg(target_arch = "wasm32")]
-    _padding_2: f32,
 }
 
 impl SkyboxCustomMaterial {
-    pub fn new(time_t0: f32, sky_texture1: Handle<Image>, sky_texture2: Handle<Image>) -> Self {
+    pub fn new(time_t0: f32, sky1: SkyLayer, sky2: SkyLayer, skybox_assets: &SkyboxAssets) -> Self {
+        let uniform = |sky: &SkyLayer| {
+            sky.sun
+                .extend(if sky.cubemap.is_some() { 0.0 } else { 1.0 })
+        };
+        let texture = |sky: SkyLayer| {
+            sky.cubemap
+                .unwrap_or_else(|| skybox_assets.empty_cubemap.clone())
+        };
+
         Self {
             time_t0,
-            sky_texture1,
-            sky_texture2,
-            #[cfg(target_arch = "wasm32")]
-            _padding_0: 0.0,
-            #[cfg(target_arch = "wasm32")]
-            _padding_1: 0.0,
-            #[cfg(target_arch = "wasm32")]
-            _padding_2: 0.0,
+            sky1: uniform(&sky1),
+            sky2: uniform(&sky2),
+            sky_texture1: texture(sky1),
+            sky_texture2: texture(sky2),
         }
     }
 }
@@ -53,6 +164,69 @@ impl Material for SkyboxCustomMaterial {
     }
 }
 
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Moves the sun of a procedural sky through the day, with the light following it
+pub fn update_sky(
+    time: Res<Time>,
+    mut sky_clock: ResMut<SkyClock>,
+    mut materials: ResMut<Assets<SkyboxCustomMaterial>>,
+    q_skyboxes: Query<&MeshMaterial3d<SkyboxCustomMaterial>, With<SkyboxCustom>>,
+    mut q_sun: Query<(&mut DirectionalLight, &mut Transform, &mut Visibility), With<Sun>>,
+) {
+    if let Some(day_length) = sky_clock.sky.and_then(|sky| sky.day_length) {
+        sky_clock.hour = (sky_clock.hour + time.delta_secs() * 24.0 / day_length).rem_euclid(24.0);
+    }
+
+    if !sky_clock.is_changed() {
+        return;
+    }
+
+    let sun = sky_clock.sun();
+
+    // Every local player's skybox shares the material
+    if let Some(material) = q_skyboxes
+        .iter()
+        .next()
+        .and_then(|handle| materials.get_mut(&handle.0))
+    {
+        if material.sky1.w > 0.0 {
+            material.sky1 = sun.extend(1.0);
+        }
+    }
+
+    for (mut light, mut transform, mut visibility) in &mut q_sun {
+        *visibility = if sky_clock.sky.is_some() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+
+        // The sun fades out at the horizon as the moon comes up on the other side
+        let (direction, illuminance, color) = if sun.y >= 0.0 {
+            let elevation = smoothstep(0.0, 0.3, sun.y);
+            (
+                sun,
+                lux::AMBIENT_DAYLIGHT * smoothstep(0.0, 0.08, sun.y),
+                Color::srgb(1.0, 0.6 + 0.35 * elevation, 0.35 + 0.6 * elevation),
+            )
+        } else {
+            (
+                -sun,
+                MOON_ILLUMINANCE * smoothstep(0.0, -0.08, sun.y),
+                Color::srgb(0.6, 0.7, 1.0),
+            )
+        };
+
+        light.illuminance = illuminance;
+        light.color = color;
+        *transform = Transform::default().looking_to(-direction, Vec3::Y);
+    }
+}
+
 // from https://github.com/JonahPlusPlus/bevy_atmosphere/blob/master/src/skybox.rs
 pub fn generate_skybox_mesh() -> Mesh {
     let far = 1000.0;
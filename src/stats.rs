@@ -0,0 +1,311 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    events::{
+        DeathCause, Jumped, PlatformLanded, PlatformMissed, PlayerDied, RunEnded, RunStarted,
+        ThemeChanged,
+    },
+    player::{Player, PlayerInput},
+    profile::{Profile, Profiles},
+    storage,
+    theme::{ThemeCurrent, THEMES},
+    AppState,
+};
+
+const SAVE_KEY: &str = "stats";
+
+impl DeathCause {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fall => "FALLS",
+            Self::Hazard => "HAZARDS",
+            Self::Restart => "RESTARTS",
+        }
+    }
+}
+
+/// How the mechanics were used, over a run or a profile's lifetime
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Stats {
+    pub runs: u32,
+    pub jumps: u32,
+    pub landings: u32,
+    /// Jumps in the coyote time, after walking off a platform
+    pub coyote_saves: u32,
+    pub moving_landings: u32,
+    /// Platforms jumped over without landing on them
+    pub missed: u32,
+    /// Platforms landed in a row with a single jump each
+    pub longest_combo: u32,
+    pub longest_airborne: f32,
+    pub distance: f32,
+    pub deaths: BTreeMap<DeathCause, u32>,
+    pub themes: BTreeSet<String>,
+}
+
+impl Stats {
+    pub fn load(profile: &Profile) -> Self {
+        storage::load(&profile.save_key(SAVE_KEY)).unwrap_or_default()
+    }
+
+    fn add(&mut self, run: &Self) {
+        self.runs += run.runs;
+        self.jumps += run.jumps;
+        self.landings += run.landings;
+        self.coyote_saves += run.coyote_saves;
+        self.moving_landings += run.moving_landings;
+        self.missed += run.missed;
+        self.longest_combo = self.longest_combo.max(run.longest_combo);
+        self.longest_airborne = self.longest_airborne.max(run.longest_airborne);
+        self.distance += run.distance;
+
+        for (cause, count) in &run.deaths {
+            *self.deaths.entry(*cause).or_default() += count;
+        }
+
+        self.themes.extend(run.themes.iter().cloned());
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "RUNS: {}\nJUMPS: {}\nLANDINGS: {}\nCOYOTE SAVES: {}\nMOVING PLATFORMS: {}\nMISSED PLATFORMS: {}\nLONGEST COMBO: {}\nLONGEST AIRBORNE: {:.2}s\nDISTANCE: {:.0}m",
+            self.runs,
+            self.jumps,
+            self.landings,
+            self.coyote_saves,
+            self.moving_landings,
+            self.missed,
+            self.longest_combo,
+            self.longest_airborne,
+            self.distance,
+        );
+
+        for cause in [DeathCause::Fall, DeathCause::Hazard, DeathCause::Restart] {
+            let count = self.deaths.get(&cause).copied().unwrap_or_default();
+            text = format!("{text}\n{}: {count}", cause.name());
+        }
+
+        format!("{text}\nTHEMES: {}/{}", self.themes.len(), THEMES.len())
+    }
+}
+
+/// Statistics of the first local player's current run, added to the profile's lifetime totals when it ends
+#[derive(Resource, Default)]
+pub struct RunStats {
+    pub current: Stats,
+    pub last: Option<Stats>,
+    combo: u32,
+    jumps_since_landing: u32,
+}
+
+impl RunStats {
+    fn end_run(&mut self, profile: &Profile) {
+        if self.current.jumps == 0 {
+            return;
+        }
+
+        let mut run = std::mem::take(&mut self.current);
+        run.runs = 1;
+
+        let mut lifetime = Stats::load(profile);
+        lifetime.add(&run);
+        storage::save(&profile.save_key(SAVE_KEY), &lifetime);
+
+        self.last = Some(run);
+        self.combo = 0;
+        self.jumps_since_landing = 0;
+    }
+}
+
+// Distance and airtime, the rest comes from the gameplay events
+pub fn track_movement_stats(
+    time: Res<Time>,
+    mut run_stats: ResMut<RunStats>,
+    q_players: Query<(&Player, &LinearVelocity)>,
+) {
+    let Some((player, velocity)) = q_players.iter().find(|(player, _)| player.id == 0) else {
+        return;
+    };
+
+    if !player.started || player.is_done() {
+        return;
+    }
+
+    run_stats.current.distance += velocity.xz().length() * time.delta_secs();
+
+    run_stats.current.longest_airborne = run_stats.current.longest_airborne.max(player.air_time);
+}
+
+pub fn stats_run_started(
+    trigger: Trigger<RunStarted>,
+    theme_current: Option<Res<ThemeCurrent>>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    if trigger.event().player != 0 {
+        return;
+    }
+
+    if let Some(theme_current) = theme_current {
+        run_stats
+            .current
+            .themes
+            .insert(theme_current.theme.id.to_string());
+    }
+}
+
+pub fn stats_theme_changed(
+    trigger: Trigger<ThemeChanged>,
+    state: Res<State<AppState>>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    if *state.get() == AppState::Game {
+        run_stats
+            .current
+            .themes
+            .insert(trigger.event().theme.to_string());
+    }
+}
+
+pub fn stats_jumped(trigger: Trigger<Jumped>, mut run_stats: ResMut<RunStats>) {
+    let jumped = trigger.event();
+    if jumped.player != 0 {
+        return;
+    }
+
+    run_stats.current.jumps += 1;
+    run_stats.jumps_since_landing += 1;
+
+    if jumped.coyote {
+        run_stats.current.coyote_saves += 1;
+    }
+}
+
+pub fn stats_platform_landed(trigger: Trigger<PlatformLanded>, mut run_stats: ResMut<RunStats>) {
+    let landed = trigger.event();
+    if landed.player != 0 {
+        return;
+    }
+
+    run_stats.current.landings += 1;
+
+    if landed.moving {
+        run_stats.current.moving_landings += 1;
+    }
+
+    // Bouncing on the same platform breaks the combo
+    run_stats.combo = if run_stats.jumps_since_landing <= 1 {
+        run_stats.combo + 1
+    } else {
+        1
+    };
+    run_stats.jumps_since_landing = 0;
+    run_stats.current.longest_combo = run_stats.current.longest_combo.max(run_stats.combo);
+}
+
+pub fn stats_platform_missed(trigger: Trigger<PlatformMissed>, mut run_stats: ResMut<RunStats>) {
+    let missed = trigger.event();
+    if missed.player == 0 {
+        run_stats.current.missed += missed.count;
+    }
+}
+
+pub fn stats_player_died(trigger: Trigger<PlayerDied>, mut run_stats: ResMut<RunStats>) {
+    let died = trigger.event();
+    if died.player != 0 {
+        return;
+    }
+
+    *run_stats.current.deaths.entry(died.cause).or_default() += 1;
+    run_stats.combo = 0;
+}
+
+pub fn stats_run_ended(
+    trigger: Trigger<RunEnded>,
+    profiles: Res<Profiles>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    if trigger.event().player == 0 {
+        run_stats.end_run(profiles.current());
+    }
+}
+
+#[derive(Component)]
+pub struct StatsScreen;
+
+pub fn spawn_stats(mut commands: Commands, profiles: Res<Profiles>, run_stats: Res<RunStats>) {
+    let profile = profiles.current();
+
+    let columns = [
+        (
+            "LAST RUN".to_string(),
+            run_stats
+                .last
+                .as_ref()
+                .map_or_else(|| "NO RUN YET".to_string(), Stats::text),
+        ),
+        (
+            format!("LIFETIME: {}", profile.name),
+            Stats::load(profile).text(),
+        ),
+    ];
+
+    commands
+        .spawn((
+            StatsScreen,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(20.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        ))
+        .with_children(|c| {
+            c.spawn(Node {
+                column_gap: Val::Px(60.0),
+                ..default()
+            })
+            .with_children(|c| {
+                for (title, text) in columns {
+                    c.spawn((
+                        Text::new(format!("{title}\n\n{text}")),
+                        TextFont {
+                            font_size: 26.0,
+                            ..default()
+                        },
+                    ));
+                }
+            });
+
+            c.spawn((
+                Text::new("JUMP OR ESCAPE: BACK"),
+                TextFont {
+                    font_size: 26.0,
+                    ..default()
+                },
+            ));
+        });
+}
+
+pub fn despawn_stats(mut commands: Commands, query: Query<Entity, With<StatsScreen>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn leave_stats(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    input: PlayerInput,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) || input.jump_just_pressed(0) {
+        next_state.set(AppState::Menu);
+    }
+}
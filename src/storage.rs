@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+// Local saves: a RON file per key on native, localStorage on wasm
+
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let data = read(key)?;
+
+    ron::from_str(&data)
+        .inspect_err(|e| warn!("invalid save {key}: {e}"))
+        .ok()
+}
+
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+        Ok(data) => write(key, &data),
+        Err(e) => warn!("can't serialize save {key}: {e}"),
+    }
+}
+
+/// Folder of the game's files on native, the saves and the photos
+#[cfg(not(target_arch = "wasm32"))]
+pub fn data_dir() -> std::path::PathBuf {
+    use std::{env, path::PathBuf};
+
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_default()
+        .join("parkour")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_path(key: &str) -> std::path::PathBuf {
+    data_dir().join(format!("{key}.ron"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: &str) -> Option<String> {
+    std::fs::read_to_string(save_path(key)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(key: &str, data: &str) {
+    let path = save_path(key);
+
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, data));
+
+    if let Err(e) = result {
+        warn!("can't write save {}: {e}", path.display());
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(key: &str) -> Option<String> {
+    local_storage()?.get_item(&format!("parkour.{key}")).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(key: &str, data: &str) {
+    if let Some(storage) = local_storage() {
+        if storage.set_item(&format!("parkour.{key}"), data).is_err() {
+            warn!("can't write save {key} to localStorage");
+        }
+    }
+}
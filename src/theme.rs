@@ -2,7 +2,14 @@ use bevy::{asset::LoadState, prelude::*};
 use rand::seq::SliceRandom;
 
 use crate::{
-    skybox::{SkyboxCustom, SkyboxCustomMaterial},
+    events::ThemeChanged,
+    music::{LayerDriver, MusicLayer, MusicLoad, ThemeMusic},
+    platforms::{PlatformColliders, PlatformGeneration},
+    segments::SegmentAssets,
+    skybox::{
+        ProceduralSky, SkyClock, SkyLayer, SkyboxAssets, SkyboxCustom, SkyboxCustomMaterial,
+        ThemeSky,
+    },
     AppState,
 };
 
@@ -14,16 +21,22 @@ use crate::{
 // toktx --cubemap --t2 sky.ktx2 px.png nx.png py.png ny.png pz.png nz.png
 pub const THEME_CHANGE_CHANCE: f64 = 0.01;
 
+const PREFERRED_THEME_WEIGHT: f64 = 4.0;
+
 pub struct Theme {
     pub id: &'static str,
-    pub skybox: &'static str,
+    pub skybox: ThemeSky,
     pub platforms: &'static [&'static str],
+    /// Looping tracks, crossfaded with the skybox
+    pub music: ThemeMusic,
+    /// Biome during which the theme changes lean towards this one
+    pub biome: Option<&'static str>,
 }
 
 pub const THEMES: &[Theme] = &[
     Theme {
         id: "heaven",
-        skybox: "green_explosion.ktx2",
+        skybox: ThemeSky::Cubemap("green_explosion.ktx2"),
         platforms: &[
             "ground1.glb",
             "ground2.glb",
@@ -35,10 +48,36 @@ pub const THEMES: &[Theme] = &[
             "rock_top1.glb",
             "rock_top2.glb",
         ],
+        music: ThemeMusic {
+            base: "heaven.wav",
+            layers: &[
+                MusicLayer {
+                    file: "heaven_drums.wav",
+                    driver: LayerDriver::Difficulty,
+                    from: 0.1,
+                    to: 0.4,
+                },
+                MusicLayer {
+                    file: "heaven_lead.wav",
+                    driver: LayerDriver::Difficulty,
+                    from: 0.5,
+                    to: 1.0,
+                },
+                MusicLayer {
+                    file: "heaven_pulse.wav",
+                    driver: LayerDriver::Speed,
+                    from: 6.0,
+                    to: 9.0,
+                },
+            ],
+            stinger: "heaven_stinger.wav",
+            airborne_cutoff: 900.0,
+        },
+        biome: Some("climb"),
     },
     Theme {
         id: "space",
-        skybox: "nebula_dark.ktx2",
+        skybox: ThemeSky::Cubemap("nebula_dark.ktx2"),
         platforms: &[
             "asteroid1.glb",
             "asteroid2.glb",
@@ -46,6 +85,52 @@ pub const THEMES: &[Theme] = &[
             "asteroid4.glb",
             "asteroid5.glb",
         ],
+        music: ThemeMusic {
+            base: "space.wav",
+            layers: &[
+                MusicLayer {
+                    file: "space_bass.wav",
+                    driver: LayerDriver::Difficulty,
+                    from: 0.2,
+                    to: 0.6,
+                },
+                MusicLayer {
+                    file: "space_arp.wav",
+                    driver: LayerDriver::Speed,
+                    from: 5.5,
+                    to: 8.0,
+                },
+            ],
+            stinger: "space_stinger.wav",
+            airborne_cutoff: 600.0,
+        },
+        biome: Some("zigzag"),
+    },
+    Theme {
+        id: "daylight",
+        skybox: ThemeSky::Procedural(ProceduralSky {
+            start_hour: 8.0,
+            day_length: Some(300.0),
+        }),
+        platforms: &[
+            "ground1.glb",
+            "ground2.glb",
+            "ground1_top1.glb",
+            "ground2_top2.glb",
+            "rock.glb",
+        ],
+        music: ThemeMusic {
+            base: "daylight.wav",
+            layers: &[MusicLayer {
+                file: "daylight_drums.wav",
+                driver: LayerDriver::Difficulty,
+                from: 0.1,
+                to: 0.5,
+            }],
+            stinger: "daylight_stinger.wav",
+            airborne_cutoff: 1000.0,
+        },
+        biome: None,
     },
     // Theme {
     //     skybox: "nebula_blue.ktx2",
@@ -68,8 +153,12 @@ pub const THEMES: &[Theme] = &[
 #[derive(Clone)]
 pub struct ThemeLoad {
     pub id: &'static str,
-    pub skybox: Handle<Image>,
+    /// None for a procedural sky
+    pub skybox: Option<Handle<Image>>,
+    /// Shown without a cubemap
+    pub sky: ProceduralSky,
     pub platforms: Vec<Handle<Scene>>,
+    pub music: MusicLoad,
 }
 
 #[derive(Event)]
@@ -90,9 +179,13 @@ pub fn change_theme(
     mut commands: Commands,
     assets_server: Res<AssetServer>,
     theme_current: Option<Res<ThemeCurrent>>,
+    platform_gen: Option<Res<PlatformGeneration>>,
 ) {
     let mut rng = rand::thread_rng();
 
+    // The course doesn't depend on the theme, the theme follows the course instead
+    let biome = platform_gen.map(|platform_gen| platform_gen.biome.current());
+
     let themes = THEMES
         .iter()
         .filter(|theme| {
@@ -102,13 +195,28 @@ pub fn change_theme(
         })
         .collect::<Vec<_>>();
 
-    let theme = themes.choose(&mut rng).unwrap();
+    let theme = themes
+        .choose_weighted(&mut rng, |theme| {
+            if theme.biome.is_some() && theme.biome == biome {
+                PREFERRED_THEME_WEIGHT
+            } else {
+                1.0
+            }
+        })
+        .unwrap();
     println!("loading theme with skybox: {:?}", theme.skybox);
 
     commands.insert_resource(ThemeChange {
         theme: ThemeLoad {
             id: theme.id,
-            skybox: assets_server.load(format!("skyboxes/{}", theme.skybox)),
+            skybox: match theme.skybox {
+                ThemeSky::Cubemap(file) => Some(assets_server.load(format!("skyboxes/{file}"))),
+                ThemeSky::Procedural(_) => None,
+            },
+            sky: match theme.skybox {
+                ThemeSky::Cubemap(_) => ProceduralSky::FALLBACK,
+                ThemeSky::Procedural(sky) => sky,
+            },
             platforms: theme
                 .platforms
                 .iter()
@@ -117,6 +225,7 @@ pub fn change_theme(
                         .load(GltfAssetLabel::Scene(0).from_asset(format!("platforms/{path}")))
                 })
                 .collect(),
+            music: MusicLoad::new(&theme.music, &assets_server),
         },
     });
 }
@@ -125,12 +234,15 @@ pub fn apply_loaded_theme(
     mut commands: Commands,
     time: Res<Time>,
     assets_server: Res<AssetServer>,
-    current_state: Res<State<AppState>>,
-    mut next_state: ResMut<NextState<AppState>>,
     theme_current: Option<Res<ThemeCurrent>>,
     theme_change: Option<Res<ThemeChange>>,
-    skybox_entity: Query<Entity, With<SkyboxCustom>>,
+    skybox_assets: Res<SkyboxAssets>,
+    mut sky_clock: ResMut<SkyClock>,
+    skybox_entities: Query<Entity, With<SkyboxCustom>>,
     mut skybox_materials: ResMut<Assets<SkyboxCustomMaterial>>,
+    scenes: Res<Assets<Scene>>,
+    meshes: Res<Assets<Mesh>>,
+    mut platform_colliders: ResMut<PlatformColliders>,
 ) {
     if let Some(theme_change) = theme_change {
         let fully_loaded = theme_change
@@ -138,41 +250,95 @@ pub fn apply_loaded_theme(
             .platforms
             .iter()
             .all(|handle| matches!(assets_server.load_state(handle), LoadState::Loaded))
-            && matches!(
-                assets_server.load_state(&theme_change.theme.skybox),
-                LoadState::Loaded
-            );
+            && theme_change.theme.skybox.as_ref().is_none_or(|handle| {
+                matches!(
+                    assets_server.load_state(handle),
+                    LoadState::Loaded | LoadState::Failed(_)
+                )
+            });
 
         if fully_loaded {
+            // The theme changes once its platform colliders are built in the background
+            platform_colliders.build(&theme_change.theme.platforms, &scenes, &meshes);
+        }
+
+        if fully_loaded && platform_colliders.is_built(&theme_change.theme.platforms) {
+            let mut theme = theme_change.theme.clone();
+
+            if theme.skybox.as_ref().is_some_and(|handle| {
+                matches!(assets_server.load_state(handle), LoadState::Failed(_))
+            }) {
+                warn!(
+                    "can't load the skybox of theme {}, using a procedural sky",
+                    theme.id
+                );
+                theme.skybox = None;
+            }
+
+            let previous = theme_current.as_ref().map(|current| current.theme.id);
+
+            // The previous sky keeps its sun where it was
+            let previous_sun = sky_clock.sun();
+            sky_clock.start(theme.skybox.is_none().then_some(theme.sky));
+
             let mut time_t0 = time.elapsed_secs_wrapped();
-            let sky_texture1 = theme_change.theme.skybox.clone();
-            let sky_texture2 = theme_current.map_or_else(
+            let sky1 = SkyLayer {
+                cubemap: theme.skybox.clone(),
+                sun: sky_clock.sun(),
+            };
+            let sky2 = theme_current.map_or_else(
                 || {
-                    time_t0 += 30.0; // prevent shader from running for nothing when tex1 == tex2
-                    sky_texture1.clone()
+                    time_t0 += 30.0; // prevent shader from running for nothing when sky1 == sky2
+                    SkyLayer {
+                        cubemap: sky1.cubemap.clone(),
+                        sun: sky1.sun,
+                    }
+                },
+                |theme_current| SkyLayer {
+                    cubemap: theme_current.theme.skybox.clone(),
+                    sun: previous_sun,
                 },
-                |theme_current| theme_current.theme.skybox.clone(),
             );
 
-            commands
-                .entity(skybox_entity.single())
-                .insert(MeshMaterial3d(skybox_materials.add(
-                    SkyboxCustomMaterial::new(time_t0, sky_texture1, sky_texture2),
-                )));
+            let skybox_material = MeshMaterial3d(skybox_materials.add(SkyboxCustomMaterial::new(
+                time_t0,
+                sky1,
+                sky2,
+                &skybox_assets,
+            )));
 
-            commands.insert_resource(ThemeCurrent {
-                theme: theme_change.theme.clone(),
-            });
+            // One skybox per local player
+            for skybox_entity in &skybox_entities {
+                commands
+                    .entity(skybox_entity)
+                    .insert(skybox_material.clone());
+            }
 
-            commands.remove_resource::<ThemeChange>();
+            let id = theme.id;
+            commands.insert_resource(ThemeCurrent { theme });
 
-            if let AppState::Loading = current_state.get() {
-                next_state.set(AppState::Game);
-            }
+            commands.remove_resource::<ThemeChange>();
+            commands.trigger(ThemeChanged {
+                theme: id,
+                previous,
+            });
         }
     }
 }
 
+/// Leave the loading screen once the first theme is applied and the segment prefabs are done loading,
+/// a run started before would pick its segments differently
+pub fn finish_loading(
+    assets_server: Res<AssetServer>,
+    theme_current: Option<Res<ThemeCurrent>>,
+    segment_assets: Res<SegmentAssets>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if theme_current.is_some() && segment_assets.is_done_loading(&assets_server) {
+        next_state.set(AppState::Menu);
+    }
+}
+
 pub fn force_theme_change(mut commands: Commands, keyboard_input: Res<ButtonInput<KeyCode>>) {
     if keyboard_input.just_pressed(KeyCode::KeyT) {
         commands.trigger(ChangeThemeRandom);
@@ -0,0 +1,169 @@
+use bevy::{pbr::NotShadowCaster, prelude::*, time::Stopwatch};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    course::Course,
+    events::{RunEndReason, RunEnded},
+    game::{Game, GameMode, RunSettings},
+    platforms::Platform,
+    player::Player,
+    profile::{Profile, Profiles},
+    run_code::RunCode,
+    storage,
+};
+
+/// Steps along the course to the goal, parallel branch platforms count once
+pub const TIME_ATTACK_PLATFORMS: u32 = 60;
+pub const TIME_ATTACK_DEFAULT_SEED: u64 = 1;
+const SPLIT_INTERVAL: u32 = 10;
+
+const SAVE_KEY: &str = "time_attack";
+
+/// Last platform of a finite course
+#[derive(Component)]
+pub struct Goal;
+
+#[derive(Resource)]
+pub struct GoalAssets {
+    beacon_mesh: Handle<Mesh>,
+    beacon_material: Handle<StandardMaterial>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TimeAttackRecord {
+    pub time: f32,
+    pub splits: Vec<f32>,
+}
+
+/// Run timer of the time-attack mode, with the profile's personal best for the course.
+/// Records are kept by run code, the difficulty and movement presets change the course
+#[derive(Resource, Default)]
+pub struct TimeAttack {
+    pub stopwatch: Stopwatch,
+    pub splits: Vec<f32>,
+    pub best: Option<TimeAttackRecord>,
+    key: String,
+}
+
+impl TimeAttack {
+    pub fn new(code: RunCode, profile: &Profile) -> Self {
+        let key = code.to_string();
+
+        Self {
+            best: storage::load::<BTreeMap<String, TimeAttackRecord>>(&profile.save_key(SAVE_KEY))
+                .and_then(|mut records| records.remove(&key)),
+            key,
+            ..default()
+        }
+    }
+
+    /// Difference with the personal best at the last split
+    pub fn split_delta(&self) -> Option<f32> {
+        let index = self.splits.len().checked_sub(1)?;
+        let best = self.best.as_ref()?.splits.get(index)?;
+        Some(self.splits[index] - best)
+    }
+}
+
+pub fn load_goal_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(GoalAssets {
+        beacon_mesh: meshes.add(Cylinder::new(0.4, 12.0)),
+        beacon_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(1.0, 0.8, 0.2, 0.5),
+            emissive: LinearRgba::rgb(4.0, 3.0, 0.5),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+pub fn spawn_goal(commands: &mut Commands, goal_assets: &GoalAssets, platform: Entity) {
+    commands.entity(platform).insert(Goal).with_children(|c| {
+        c.spawn((
+            Mesh3d(goal_assets.beacon_mesh.clone()),
+            MeshMaterial3d(goal_assets.beacon_material.clone()),
+            Transform::from_translation(Vec3::Y * 6.0),
+            NotShadowCaster,
+        ));
+    });
+}
+
+pub fn update_time_attack(
+    mut commands: Commands,
+    time: Res<Time>,
+    run_settings: Res<RunSettings>,
+    profiles: Res<Profiles>,
+    course: Res<Course>,
+    mut game: ResMut<Game>,
+    mut time_attack: ResMut<TimeAttack>,
+    mut q_players: Query<(Entity, &mut Player)>,
+    q_goal: Query<&Platform, With<Goal>>,
+) {
+    if run_settings.mode != GameMode::TimeAttack || !game.started || game.finished {
+        return;
+    }
+
+    time_attack.stopwatch.tick(time.delta());
+    let elapsed = time_attack.stopwatch.elapsed_secs();
+
+    for (entity, mut player) in &mut q_players {
+        let Some(current) = course.current(player.id) else {
+            continue;
+        };
+
+        // Split every few platforms along the course, against the personal best when alone
+        if run_settings.players == 1
+            && course.steps(player.id) >= SPLIT_INTERVAL * (time_attack.splits.len() as u32 + 1)
+        {
+            time_attack.splits.push(elapsed);
+        }
+
+        // Finish line
+        if player.finish_time.is_some() || !q_goal.iter().any(|goal| goal.index == current.index) {
+            continue;
+        }
+
+        player.finish_time = Some(elapsed);
+
+        // The race goes on until every player is done
+        if run_settings.players == 1 {
+            game.finished = true;
+            commands.trigger_targets(
+                RunEnded {
+                    player: player.id,
+                    reason: RunEndReason::Finished,
+                    points: player.points,
+                    finish_time: player.finish_time,
+                },
+                entity,
+            );
+        }
+
+        let is_best = time_attack
+            .best
+            .as_ref()
+            .is_none_or(|best| elapsed < best.time);
+
+        // Practice runs don't count
+        if is_best && !run_settings.practicing() {
+            let record = TimeAttackRecord {
+                time: elapsed,
+                splits: time_attack.splits.clone(),
+            };
+
+            let save_key = profiles.current().save_key(SAVE_KEY);
+            let mut records =
+                storage::load::<BTreeMap<String, TimeAttackRecord>>(&save_key).unwrap_or_default();
+            records.insert(time_attack.key.clone(), record.clone());
+            storage::save(&save_key, &records);
+
+            time_attack.best = Some(record);
+        }
+    }
+}
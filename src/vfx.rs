@@ -0,0 +1,359 @@
+use avian3d::prelude::*;
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use rand::prelude::*;
+
+use crate::{
+    events::Landed,
+    player::{Player, MAX_PLAYERS},
+};
+
+const DUST_LIFETIME: f32 = 0.5;
+const DUST_SIZE: f32 = 0.25;
+
+/// Trail particles dropped behind the player, one per distance
+const TRAIL_SPACING: f32 = 0.4;
+const TRAIL_LIFETIME: f32 = 0.6;
+const TRAIL_SIZE: f32 = 0.35;
+/// No trail at the starting speed, the biggest one at twice of it
+const TRAIL_MIN_SPEED: f32 = 5.0;
+const TRAIL_FULL_SPEED: f32 = 10.0;
+
+const BOOST_GLOW_SPEED: f32 = 8.0;
+const BOOST_GLOW_LIGHT: f32 = 400_000.0;
+
+const SPAWN_IN_DURATION: f32 = 0.4;
+const CRUMBLE_DURATION: f32 = 1.0;
+const CRUMBLE_SINK_SPEED: f32 = 3.0;
+const CRUMBS: usize = 16;
+
+#[derive(Resource)]
+pub struct VfxAssets {
+    particle_mesh: Handle<Mesh>,
+    dust_material: Handle<StandardMaterial>,
+    trail_material: Handle<StandardMaterial>,
+    crumb_material: Handle<StandardMaterial>,
+    glow_mesh: Handle<Mesh>,
+    glow_material: Handle<StandardMaterial>,
+}
+
+pub fn load_vfx_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(VfxAssets {
+        particle_mesh: meshes.add(Cuboid::from_length(1.0)),
+        dust_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.9, 0.85, 0.75, 0.8),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+        trail_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.8, 0.95, 1.0, 0.5),
+            emissive: LinearRgba::rgb(1.0, 2.0, 3.0),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+        crumb_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.45, 0.4, 0.35),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+        glow_mesh: meshes.add(Sphere::new(1.2)),
+        glow_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(1.0, 0.8, 0.3, 0.3),
+            emissive: LinearRgba::rgb(6.0, 4.0, 1.0),
+            alpha_mode: AlphaMode::Add,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+/// Short-lived cube, shrinking until it's gone
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec3,
+    gravity: f32,
+    size: f32,
+    life: Timer,
+}
+
+fn spawn_particle(
+    commands: &mut Commands,
+    mesh: &Handle<Mesh>,
+    material: &Handle<StandardMaterial>,
+    position: Vec3,
+    particle: Particle,
+) {
+    commands.spawn((
+        Mesh3d(mesh.clone()),
+        MeshMaterial3d(material.clone()),
+        Transform::from_translation(position)
+            .with_rotation(Quat::from_rng(&mut rand::thread_rng()))
+            .with_scale(Vec3::splat(particle.size)),
+        NotShadowCaster,
+        particle,
+    ));
+}
+
+pub fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut Particle)>,
+) {
+    for (entity, mut transform, mut particle) in &mut query {
+        if particle.life.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        particle.velocity.y += particle.gravity * time.delta_secs();
+        transform.translation += particle.velocity * time.delta_secs();
+        transform.scale = Vec3::splat(particle.size * particle.life.fraction_remaining());
+    }
+}
+
+// Dust ring at the feet, bigger for harder landings
+pub fn landing_dust(
+    trigger: Trigger<Landed>,
+    mut commands: Commands,
+    vfx_assets: Res<VfxAssets>,
+    q_players: Query<&Transform, With<Player>>,
+) {
+    let Ok(transform) = q_players.get(trigger.entity()) else {
+        return;
+    };
+
+    let impact = trigger.event().impact;
+    let count = 6 + (impact as usize).min(14);
+    let speed = 1.5 + impact * 0.2;
+    let feet = transform.translation - Vec3::Y * 0.9;
+    let mut rng = rand::thread_rng();
+
+    for i in 0..count {
+        let angle = i as f32 / count as f32 * std::f32::consts::TAU + rng.gen_range(-0.2..0.2);
+        let direction = Vec3::new(angle.cos(), rng.gen_range(0.1..0.4), angle.sin());
+
+        spawn_particle(
+            &mut commands,
+            &vfx_assets.particle_mesh,
+            &vfx_assets.dust_material,
+            feet,
+            Particle {
+                velocity: direction * speed * rng.gen_range(0.7..1.0),
+                gravity: -2.0,
+                size: DUST_SIZE * rng.gen_range(0.7..1.3),
+                life: Timer::from_seconds(DUST_LIFETIME, TimerMode::Once),
+            },
+        );
+    }
+}
+
+pub fn speed_trails(
+    mut commands: Commands,
+    time: Res<Time>,
+    vfx_assets: Res<VfxAssets>,
+    mut travelled: Local<[f32; MAX_PLAYERS]>,
+    q_players: Query<(&Player, &Transform, &LinearVelocity)>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (player, transform, velocity) in &q_players {
+        let strength = ((player.forward_speed - TRAIL_MIN_SPEED)
+            / (TRAIL_FULL_SPEED - TRAIL_MIN_SPEED))
+            .clamp(0.0, 1.0);
+
+        if !player.started || player.is_done() || strength == 0.0 {
+            travelled[player.id] = 0.0;
+            continue;
+        }
+
+        travelled[player.id] += velocity.length() * time.delta_secs();
+
+        // Behind the body, out of the player's own view
+        let behind = transform.translation - velocity.normalize_or_zero() * 1.2;
+
+        while travelled[player.id] > TRAIL_SPACING {
+            travelled[player.id] -= TRAIL_SPACING;
+
+            spawn_particle(
+                &mut commands,
+                &vfx_assets.particle_mesh,
+                &vfx_assets.trail_material,
+                behind + Vec3::new(rng.gen_range(-0.3..0.3), rng.gen_range(-0.3..0.3), 0.0),
+                Particle {
+                    velocity: Vec3::ZERO,
+                    gravity: 0.0,
+                    size: TRAIL_SIZE * strength,
+                    life: Timer::from_seconds(TRAIL_LIFETIME, TimerMode::Once),
+                },
+            );
+        }
+    }
+}
+
+/// Halo and light around a player while the jump boost pushes them up
+#[derive(Component, Default)]
+pub struct BoostGlow(f32);
+
+pub fn spawn_boost_glows(
+    mut commands: Commands,
+    vfx_assets: Res<VfxAssets>,
+    q_players: Query<Entity, Added<Player>>,
+) {
+    for entity in &q_players {
+        commands.entity(entity).with_child((
+            BoostGlow::default(),
+            Mesh3d(vfx_assets.glow_mesh.clone()),
+            MeshMaterial3d(vfx_assets.glow_material.clone()),
+            PointLight {
+                color: Color::srgb(1.0, 0.8, 0.4),
+                intensity: 0.0,
+                shadows_enabled: false,
+                ..default()
+            },
+            NotShadowCaster,
+            Visibility::Hidden,
+        ));
+    }
+}
+
+pub fn update_boost_glows(
+    time: Res<Time>,
+    q_players: Query<&Player>,
+    mut q_glows: Query<(
+        &Parent,
+        &mut BoostGlow,
+        &mut Transform,
+        &mut PointLight,
+        &mut Visibility,
+    )>,
+) {
+    for (parent, mut glow, mut transform, mut light, mut visibility) in &mut q_glows {
+        let boosting = q_players.get(parent.get()).is_ok_and(Player::is_boosting);
+
+        let target = if boosting { 1.0 } else { 0.0 };
+        glow.0 += (target - glow.0) * (1.0 - (-BOOST_GLOW_SPEED * time.delta_secs()).exp());
+
+        let shown = glow.0 > 0.01;
+        let new_visibility = if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+
+        if shown {
+            transform.scale = Vec3::splat(glow.0);
+            light.intensity = BOOST_GLOW_LIGHT * glow.0;
+        }
+    }
+}
+
+/// New platform growing to its size
+#[derive(Component)]
+pub struct SpawnIn {
+    scale: Vec3,
+    timer: Timer,
+}
+
+impl SpawnIn {
+    /// Start of the animation, a zero scale would break the colliders
+    pub const START_SCALE: f32 = 0.01;
+
+    pub fn new(scale: Vec3) -> Self {
+        Self {
+            scale,
+            timer: Timer::from_seconds(SPAWN_IN_DURATION, TimerMode::Once),
+        }
+    }
+}
+
+pub fn animate_spawn_in(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut SpawnIn)>,
+) {
+    for (entity, mut transform, mut spawn_in) in &mut query {
+        spawn_in.timer.tick(time.delta());
+
+        // Ease out with a slight overshoot
+        let t = spawn_in.timer.fraction();
+        let c = 1.7;
+        let eased = 1.0 + (c + 1.0) * (t - 1.0).powi(3) + c * (t - 1.0).powi(2);
+        transform.scale = spawn_in.scale * eased.max(SpawnIn::START_SCALE);
+
+        if spawn_in.timer.finished() {
+            commands.entity(entity).remove::<SpawnIn>();
+        }
+    }
+}
+
+/// Platform breaking apart and sinking before it's despawned
+#[derive(Component)]
+pub struct Crumbling {
+    scale: Vec3,
+    timer: Timer,
+}
+
+impl Crumbling {
+    pub fn new(scale: Vec3) -> Self {
+        Self {
+            scale,
+            timer: Timer::from_seconds(CRUMBLE_DURATION, TimerMode::Once),
+        }
+    }
+}
+
+pub fn crumble_platforms(
+    mut commands: Commands,
+    time: Res<Time>,
+    vfx_assets: Res<VfxAssets>,
+    mut query: Query<(Entity, &mut Transform, &GlobalTransform, Mut<Crumbling>)>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (entity, mut transform, global_transform, mut crumbling) in &mut query {
+        if crumbling.is_added() {
+            let spread = 2.0 * crumbling.scale.max_element();
+
+            for _ in 0..CRUMBS {
+                let offset = Vec3::new(
+                    rng.gen_range(-spread..spread),
+                    rng.gen_range(-0.5..0.5),
+                    rng.gen_range(-spread..spread),
+                );
+
+                spawn_particle(
+                    &mut commands,
+                    &vfx_assets.particle_mesh,
+                    &vfx_assets.crumb_material,
+                    global_transform.translation() + offset,
+                    Particle {
+                        velocity: offset * 0.5,
+                        gravity: -9.81,
+                        size: rng.gen_range(0.2..0.5),
+                        life: Timer::from_seconds(
+                            CRUMBLE_DURATION * rng.gen_range(1.0..1.5),
+                            TimerMode::Once,
+                        ),
+                    },
+                );
+            }
+        }
+
+        if crumbling.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let remaining = crumbling.timer.fraction_remaining();
+        transform.scale = crumbling.scale * remaining.max(SpawnIn::START_SCALE);
+        transform.translation.y -= CRUMBLE_SINK_SPEED * time.delta_secs();
+    }
+}